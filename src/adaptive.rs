@@ -0,0 +1,83 @@
+use crate::events::Divergence;
+use crate::integrator::Integrator;
+use crate::web::Spiderweb;
+
+/// Below this much energy the web is close to rest, so relative growth from
+/// one substep to the next says nothing about stability
+const MIN_ENERGY: f64 = 1.0;
+
+/// Settings for splitting a step into stable substeps and for catching
+/// substeps that blow up anyway
+#[derive(Copy, Clone, Debug)]
+pub struct AdaptiveStepping {
+    pub enabled: bool,
+    /// Fraction of the estimated stable timestep to actually use
+    pub stability_factor: f64,
+    /// Most substeps a single step may be split into
+    pub max_substeps: usize,
+    /// How many times the web's energy may grow over one substep before it
+    /// counts as diverged
+    pub max_energy_growth: f64,
+    /// Smallest timestep a diverging substep is retried with
+    pub min_timestep: f64,
+}
+
+impl Default for AdaptiveStepping {
+    fn default() -> Self {
+        AdaptiveStepping {
+            enabled: false,
+            stability_factor: 0.5,
+            max_substeps: 100,
+            max_energy_growth: 10.0,
+            min_timestep: 1e-6,
+        }
+    }
+}
+
+impl AdaptiveStepping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Estimates the largest timestep an explicit integrator can take without
+    /// blowing up, from the stiffest and most damped strand relative to the
+    /// mass of its particles, at how stiff each strand's silk is at its current
+    /// stretch. Implicit schemes have no such limit.
+    pub fn stable_timestep(&self, web: &Spiderweb, integrator: Integrator) -> f64 {
+        if matches!(integrator, Integrator::BackwardEuler | Integrator::Xpbd) {
+            return f64::INFINITY;
+        }
+
+        let inverse_mass = |idx: usize| {
+            let particle = &web.particles[idx];
+            if particle.fixed { 0.0 } else { 1.0 / particle.mass }
+        };
+        let mut stable_timestep = f64::INFINITY;
+        for strand in &web.strands {
+            let w = inverse_mass(strand.start) + inverse_mass(strand.end);
+            // 2 / omega for the spring alone, and 2 / (c * w) for the dashpot alone
+            let len = (web.particles[strand.start].position - web.particles[strand.end].position).norm();
+            let rate = (strand.tangent_stiffness(len) * w).sqrt() + strand.damping * w;
+            if rate > 0.0 {
+                stable_timestep = stable_timestep.min(2.0 / rate);
+            }
+        }
+        stable_timestep * self.stability_factor
+    }
+
+    /// Checks a web that had `energy_before` at the start of a substep for
+    /// signs that the substep diverged
+    pub fn check(&self, web: &Spiderweb, energy_before: f64) -> Option<Divergence> {
+        let non_finite = web.particles.iter()
+            .any(|p| !p.position.iter().chain(p.velocity.iter()).all(|x| x.is_finite()));
+        if non_finite {
+            return Some(Divergence::NonFinite);
+        }
+
+        let energy_after = web.energy();
+        if energy_after > self.max_energy_growth * energy_before.max(MIN_ENERGY) {
+            return Some(Divergence::EnergyJump { before: energy_before, after: energy_after });
+        }
+        None
+    }
+}
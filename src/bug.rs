@@ -0,0 +1,240 @@
+use nalgebra::Vector3;
+use rand::Rng;
+
+use crate::web::{Particle, ParticleType};
+
+/// How a free bug decides where to fly
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Behaviour {
+    /// Keeps flying whichever way it's already going
+    Straight,
+    /// Flies along a heading that drifts randomly, turning by about
+    /// `turn_rate` radians over a second
+    Wander { heading: Vector3<f64>, turn_rate: f64 },
+    /// Heads for a light
+    SeekLight { light: Vector3<f64> },
+}
+
+impl Behaviour {
+    /// A wandering behaviour starting off in a random direction
+    pub fn wander<R: Rng>(rng: &mut R, turn_rate: f64) -> Self {
+        Behaviour::Wander { heading: random_unit_vector(rng), turn_rate }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Behaviour::Straight => "Straight",
+            Behaviour::Wander { .. } => "Wander",
+            Behaviour::SeekLight { .. } => "Seek Light",
+        }
+    }
+}
+
+/// How well free bugs see strands, and so how well they avoid them
+#[derive(Copy, Clone, Debug)]
+pub struct Senses {
+    /// How far away a bug can see a strand
+    pub sight_range: f64,
+    /// How visible strands are, from 0 for invisible silk that bugs never
+    /// avoid to 1 for silk they always steer away from
+    pub strand_visibility: f64,
+    /// How hard a bug steers away from a fully visible strand right in front
+    /// of it, compared to where it wants to go
+    pub avoidance: f64,
+}
+
+impl Default for Senses {
+    fn default() -> Self {
+        Senses {
+            sight_range: 0.5,
+            strand_visibility: 0.0,
+            avoidance: 2.0,
+        }
+    }
+}
+
+impl Senses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A bug flying free of the web
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bug {
+    pub particle: Particle,
+    pub behaviour: Behaviour,
+    /// Which way the bug is thrusting, or zero if it isn't
+    pub heading: Vector3<f64>,
+    pub radius: f64,
+    /// How hard the bug pushes when it struggles in the web
+    pub struggle_force: f64,
+    /// Tension a strand has to pull on the bug with to tear it free of the web
+    pub adhesion: f64,
+    /// Set when the bug has just pulled free of the web, until it's no longer
+    /// touching it, so it isn't caught again straight away
+    pub escaping: bool,
+}
+
+impl Bug {
+    pub fn new(position: Vector3<f64>, velocity: Vector3<f64>, mass: f64) -> Self {
+        Bug {
+            particle: Particle::new(position, velocity, mass, false, ParticleType::Bug),
+            behaviour: Behaviour::Straight,
+            heading: Vector3::zeros(),
+            radius: 0.03,
+            struggle_force: 2.0,
+            adhesion: 20.0,
+            escaping: false,
+        }
+    }
+
+    /// Moves the behaviour on by `timestep`, drawing any randomness from `rng`
+    pub fn update_behaviour<R: Rng>(&mut self, rng: &mut R, timestep: f64) {
+        if let Behaviour::Wander { heading, turn_rate } = &mut self.behaviour {
+            // A random walk over directions, so the turn grows with the square root of time
+            let turn = random_unit_vector(rng) * (*turn_rate * timestep.sqrt());
+            *heading = (*heading + turn).try_normalize(1e-9).unwrap_or(*heading);
+        }
+    }
+
+    /// Which way the bug wants to go, ignoring the web
+    pub fn desired_heading(&self) -> Vector3<f64> {
+        let desired = match self.behaviour {
+            Behaviour::Straight => self.particle.velocity,
+            Behaviour::Wander { heading, .. } => heading,
+            Behaviour::SeekLight { light } => light - self.particle.position,
+        };
+        desired.try_normalize(1e-9).unwrap_or_else(Vector3::zeros)
+    }
+}
+
+/// A normally distributed quantity that can't go below zero
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spread {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl Spread {
+    pub const fn new(mean: f64, std_dev: f64) -> Self {
+        Spread { mean, std_dev }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        if self.std_dev <= 0.0 {
+            return self.mean;
+        }
+        loop {
+            // Box-Muller transform of two uniform samples
+            let u: f64 = 1.0 - rng.gen::<f64>();
+            let v: f64 = rng.gen();
+            let sample = self.mean + self.std_dev * (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+            if sample > 0.0 {
+                return sample;
+            }
+        }
+    }
+}
+
+/// What a kind of bug is like, with the spread of sizes, weights and speeds
+/// individual bugs of the kind are drawn from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BugSpecies {
+    pub name: &'static str,
+    pub radius: Spread,
+    pub mass: Spread,
+    pub speed: Spread,
+    pub adhesion: f64,
+    pub struggle_force: f64,
+}
+
+impl BugSpecies {
+    pub const FRUIT_FLY: BugSpecies = BugSpecies {
+        name: "Fruit Fly",
+        radius: Spread::new(0.008, 0.001),
+        mass: Spread::new(0.2, 0.03),
+        speed: Spread::new(0.8, 0.2),
+        adhesion: 5.0,
+        struggle_force: 0.5,
+    };
+    pub const MOSQUITO: BugSpecies = BugSpecies {
+        name: "Mosquito",
+        radius: Spread::new(0.01, 0.0015),
+        mass: Spread::new(0.3, 0.05),
+        speed: Spread::new(1.0, 0.2),
+        adhesion: 5.0,
+        struggle_force: 0.8,
+    };
+    /// Moths shed the scales on their wings, so they stick less well than
+    /// their size suggests
+    pub const MOTH: BugSpecies = BugSpecies {
+        name: "Moth",
+        radius: Spread::new(0.03, 0.006),
+        mass: Spread::new(2.0, 0.4),
+        speed: Spread::new(1.5, 0.3),
+        adhesion: 8.0,
+        struggle_force: 5.0,
+    };
+    pub const BEE: BugSpecies = BugSpecies {
+        name: "Bee",
+        radius: Spread::new(0.025, 0.003),
+        mass: Spread::new(3.0, 0.5),
+        speed: Spread::new(2.5, 0.5),
+        adhesion: 20.0,
+        struggle_force: 15.0,
+    };
+    pub const PRESETS: [BugSpecies; 4] = [Self::FRUIT_FLY, Self::MOSQUITO, Self::MOTH, Self::BEE];
+
+    /// A bug of this species at `position`, flying towards `direction` at a
+    /// speed drawn from the species' spread, as are its size and mass
+    pub fn spawn<R: Rng>(&self, rng: &mut R, position: Vector3<f64>, direction: Vector3<f64>) -> Bug {
+        let velocity = direction.try_normalize(1e-9).unwrap_or_else(Vector3::zeros) * self.speed.sample(rng);
+        let mut bug = Bug::new(position, velocity, self.mass.sample(rng));
+        bug.radius = self.radius.sample(rng);
+        bug.adhesion = self.adhesion;
+        bug.struggle_force = self.struggle_force;
+        bug
+    }
+}
+
+/// A bug stuck in the web, struggling to get free
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CapturedBug {
+    /// Index of the bug's particle in the web
+    pub particle_idx: usize,
+    /// The bug as it was when it was caught, to fly off as if it escapes
+    pub bug: Bug,
+    pub captured_at: f64,
+    /// Which way the bug is pushing right now
+    pub struggle_direction: Vector3<f64>,
+    /// When the bug next pushes in a new direction
+    pub next_struggle: f64,
+}
+
+impl CapturedBug {
+    pub fn new(particle_idx: usize, bug: Bug, captured_at: f64) -> Self {
+        CapturedBug {
+            particle_idx,
+            bug,
+            captured_at,
+            struggle_direction: Vector3::zeros(),
+            next_struggle: captured_at,
+        }
+    }
+
+    pub fn struggle_force(&self) -> Vector3<f64> {
+        self.struggle_direction * self.bug.struggle_force
+    }
+}
+
+/// A random direction, evenly spread over the sphere
+pub fn random_unit_vector<R: Rng>(rng: &mut R) -> Vector3<f64> {
+    loop {
+        let v = Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+        let norm_squared: f64 = v.norm_squared();
+        if norm_squared > 1e-6 && norm_squared <= 1.0 {
+            return v / norm_squared.sqrt();
+        }
+    }
+}
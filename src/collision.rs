@@ -0,0 +1,90 @@
+use nalgebra::Vector3;
+
+use crate::web::Particle;
+
+/// How close, as a fraction of the bug's radius, the bug has to get to a strand
+/// for the search for the time of impact to count it as touching
+const CONTACT_TOLERANCE: f64 = 1e-3;
+
+/// Most steps the search for the time of impact takes before giving up on a
+/// bug that only grazes a strand
+const MAX_ADVANCEMENT_STEPS: usize = 256;
+
+/// Where and when a bug first touches a strand during a step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    /// Fraction of the step, from 0 to 1, after which the bug touches the strand
+    pub time_of_impact: f64,
+    /// Where on the strand the bug touches it, from 0 at its start particle to
+    /// 1 at its end particle
+    pub strand_param: f64,
+}
+
+/// A point moving in a straight line over a step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub from: Vector3<f64>,
+    pub to: Vector3<f64>,
+}
+
+impl Sweep {
+    /// How a particle moved over the last step
+    pub fn of(particle: &Particle) -> Self {
+        Sweep {
+            from: particle.prev_position,
+            to: particle.position,
+        }
+    }
+
+    /// Where the point is after `time`, as a fraction of the step
+    pub fn at(&self, time: f64) -> Vector3<f64> {
+        self.from.lerp(&self.to, time)
+    }
+
+    pub fn distance(&self) -> f64 {
+        (self.to - self.from).norm()
+    }
+}
+
+/// Distance from `pos` to the segment from `start` to `end`, and how far along
+/// the segment the closest point is
+pub fn closest_point_on_segment(pos: Vector3<f64>, start: Vector3<f64>, end: Vector3<f64>) -> (f64, f64) {
+    let segment = end - start;
+    let length_squared = segment.norm_squared();
+    let t = if length_squared > 0.0 {
+        ((pos - start).dot(&segment) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    ((start + segment * t - pos).norm(), t)
+}
+
+/// Finds when a sphere of `radius` around the bug first touches the strand
+/// between `start` and `end` while all three move over a step, so a bug can't
+/// pass through a strand between steps however fast either of them moves.
+///
+/// Uses conservative advancement: every point on the strand moves at most as
+/// far as its furthest moving end, so the gap between bug and strand can't
+/// close faster than the bug and that end together. Moving forward by the gap
+/// over that rate can never skip past the first contact.
+pub fn sweep_sphere_against_segment(bug: Sweep, start: Sweep, end: Sweep, radius: f64) -> Option<Contact> {
+    let max_closing = bug.distance() + start.distance().max(end.distance());
+    let mut time = 0.0;
+    for _ in 0..MAX_ADVANCEMENT_STEPS {
+        let (distance, strand_param) = closest_point_on_segment(bug.at(time), start.at(time), end.at(time));
+        let gap = distance - radius;
+        if gap <= CONTACT_TOLERANCE * radius {
+            return Some(Contact { time_of_impact: time, strand_param });
+        }
+        // Also stops on NaN positions, and when nothing moves
+        time += gap / max_closing;
+        if time.is_nan() || time > 1.0 {
+            return None;
+        }
+    }
+
+    // Only a bug skimming along just outside the strand gets here, so fall back
+    // to checking where everything ends up
+    let (distance, strand_param) = closest_point_on_segment(bug.to, start.to, end.to);
+    (distance <= radius).then_some(Contact { time_of_impact: 1.0, strand_param })
+}
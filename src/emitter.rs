@@ -0,0 +1,170 @@
+use std::error::Error;
+
+use nalgebra::Vector3;
+use rand::Rng;
+
+use crate::bug::{random_unit_vector, Behaviour, Bug, BugSpecies};
+
+/// A bug an emitter's script releases at a set time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScheduledSpawn {
+    pub time: f64,
+    pub position: Vector3<f64>,
+    pub direction: Vector3<f64>,
+}
+
+/// Where and when an emitter releases bugs
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmitterKind {
+    /// Releases `rate` bugs a second from one point, flying in `direction`
+    /// give or take up to `spread` radians
+    Point { position: Vector3<f64>, direction: Vector3<f64>, spread: f64, rate: f64 },
+    /// Releases `rate` bugs a second from random points on a rectangle,
+    /// all flying in `direction`. The rectangle is centred on `centre` and
+    /// reaches `half_width` and `half_height` out from it.
+    Curtain { centre: Vector3<f64>, half_width: Vector3<f64>, half_height: Vector3<f64>, direction: Vector3<f64>, rate: f64 },
+    /// Releases bugs from random points in a ball, flying towards `target`.
+    /// They arrive independently of each other at `rate` a second on average,
+    /// so the gaps between them are exponentially distributed.
+    Swarm { centre: Vector3<f64>, radius: f64, target: Vector3<f64>, rate: f64 },
+    /// Releases bugs exactly as listed, in order of time
+    Scripted { spawns: Vec<ScheduledSpawn> },
+}
+
+/// Releases bugs of one species into the simulation over time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Emitter {
+    pub kind: EmitterKind,
+    pub species: BugSpecies,
+    /// Behaviour given to every bug. Wandering bugs each start off in their
+    /// own random direction.
+    pub behaviour: Behaviour,
+    pub lifespan: f64,
+    /// When the next bug is due, once the first has been scheduled
+    next_spawn: Option<f64>,
+    /// Index of the next spawn in a script, once the emitter has started
+    next_scripted: Option<usize>,
+}
+
+impl Emitter {
+    pub fn new(kind: EmitterKind, species: BugSpecies) -> Self {
+        Emitter {
+            kind,
+            species,
+            behaviour: Behaviour::Straight,
+            lifespan: f64::INFINITY,
+            next_spawn: None,
+            next_scripted: None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            EmitterKind::Point { .. } => "Point",
+            EmitterKind::Curtain { .. } => "Curtain",
+            EmitterKind::Swarm { .. } => "Swarm",
+            EmitterKind::Scripted { .. } => "Scripted",
+        }
+    }
+
+    /// Every bug due to be released by time `now`. The emitter starts the
+    /// first time it's run, so one added partway through a simulation
+    /// doesn't release all the bugs it would have since the start, and
+    /// skips any of its script that's already passed.
+    pub fn emit<R: Rng>(&mut self, rng: &mut R, now: f64) -> Vec<Bug> {
+        let mut bugs = Vec::new();
+        if let EmitterKind::Scripted { spawns } = &self.kind {
+            let next_scripted = self.next_scripted.get_or_insert_with(|| spawns.partition_point(|spawn| spawn.time < now));
+            while let Some(spawn) = spawns.get(*next_scripted).filter(|spawn| spawn.time <= now) {
+                bugs.push(self.species.spawn(rng, spawn.position, spawn.direction));
+                *next_scripted += 1;
+            }
+        } else {
+            let mut next_spawn = match self.next_spawn {
+                Some(next_spawn) => next_spawn,
+                None => now + self.first_spawn(rng),
+            };
+            while next_spawn <= now {
+                let (position, direction) = self.place(rng);
+                bugs.push(self.species.spawn(rng, position, direction));
+                next_spawn += self.interval(rng);
+            }
+            self.next_spawn = Some(next_spawn);
+        }
+
+        for bug in &mut bugs {
+            bug.particle.lifespan = self.lifespan;
+            bug.behaviour = match self.behaviour {
+                Behaviour::Wander { turn_rate, .. } => Behaviour::wander(rng, turn_rate),
+                behaviour => behaviour,
+            };
+        }
+        bugs
+    }
+
+    fn rate(&self) -> f64 {
+        match self.kind {
+            EmitterKind::Point { rate, .. } | EmitterKind::Curtain { rate, .. } | EmitterKind::Swarm { rate, .. } => rate,
+            EmitterKind::Scripted { .. } => 0.0,
+        }
+    }
+
+    /// Time from the emitter starting to its first bug. Steady emitters start
+    /// straight away, while a swarm's first bug takes as long to arrive as
+    /// any other.
+    fn first_spawn<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self.kind {
+            EmitterKind::Swarm { .. } => self.interval(rng),
+            _ => 0.0,
+        }
+    }
+
+    /// Time from one bug to the next
+    fn interval<R: Rng>(&self, rng: &mut R) -> f64 {
+        let rate = self.rate();
+        if rate <= 0.0 {
+            return f64::INFINITY;
+        }
+        match self.kind {
+            EmitterKind::Swarm { .. } => -(1.0 - rng.gen::<f64>()).ln() / rate,
+            _ => 1.0 / rate,
+        }
+    }
+
+    /// Where a new bug starts and which way it heads
+    fn place<R: Rng>(&self, rng: &mut R) -> (Vector3<f64>, Vector3<f64>) {
+        match &self.kind {
+            EmitterKind::Point { position, direction, spread, .. } => {
+                let wobble = random_unit_vector(rng) * spread.sin() * rng.gen::<f64>();
+                (*position, direction.normalize() + wobble)
+            }
+            EmitterKind::Curtain { centre, half_width, half_height, direction, .. } => {
+                let position = centre + half_width * rng.gen_range(-1.0..1.0) + half_height * rng.gen_range(-1.0..1.0);
+                (position, *direction)
+            }
+            EmitterKind::Swarm { centre, radius, target, .. } => {
+                // The cube root spreads bugs evenly through the ball's volume
+                let position = centre + random_unit_vector(rng) * (radius * rng.gen::<f64>().cbrt());
+                (position, target - position)
+            }
+            EmitterKind::Scripted { .. } => unreachable!("scripted spawns are placed by their script"),
+        }
+    }
+}
+
+/// Reads a spawn script from a CSV file with a header row and the columns
+/// time, x, y, z, dx, dy, dz, sorted by time
+pub fn read_spawn_script(path: &str) -> Result<Vec<ScheduledSpawn>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut spawns = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let values = record.iter().map(|field| field.trim().parse::<f64>()).collect::<Result<Vec<f64>, _>>()?;
+        let [time, x, y, z, dx, dy, dz] = values[..] else {
+            return Err(format!("expected 7 columns but found {}", values.len()).into());
+        };
+        spawns.push(ScheduledSpawn { time, position: Vector3::new(x, y, z), direction: Vector3::new(dx, dy, dz) });
+    }
+    spawns.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(spawns)
+}
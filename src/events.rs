@@ -0,0 +1,35 @@
+use nalgebra::Vector3;
+
+use crate::collision::Contact;
+
+/// Why a step was judged to have blown up
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Divergence {
+    /// A particle's position or velocity became NaN or infinite
+    NonFinite,
+    /// The web's energy grew far faster than any force could explain
+    EnergyJump { before: f64, after: f64 },
+}
+
+/// Something notable that happened during `Simulator::step`. Events are queued
+/// on the simulator until they're collected with `Simulator::drain_events`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SimEvent {
+    /// A substep diverged, so the simulation was rolled back to the start of
+    /// the substep to retry it with a smaller timestep
+    RolledBack { time: f64, divergence: Divergence, retry_timestep: f64 },
+    /// A substep still diverged at the smallest allowed timestep. An energy
+    /// jump is kept as it is, but a non-finite result is rolled back and the
+    /// rest of the step skipped.
+    Unresolved { time: f64, divergence: Divergence },
+    /// A bug hit the web and stuck to it. `time` is when it first touched the
+    /// strand, which can be partway through the step before, and
+    /// `impact_energy` is the bug's kinetic energy as it hit.
+    BugCaught { time: f64, contact: Contact, impact_energy: f64 },
+    /// A caught bug pulled free of the web after struggling in it for
+    /// `time_to_escape`
+    BugEscaped { time: f64, time_to_escape: f64 },
+    /// A free bug reached the end of its lifespan without hitting the web and
+    /// was removed
+    BugExpired { time: f64, position: Vector3<f64> },
+}
@@ -0,0 +1,314 @@
+use nalgebra::Vector3;
+
+use crate::parallel::map_range;
+use crate::simulator::Simulator;
+use crate::web::{Particle, ParticleType, SilkStrand};
+
+/// The particles a force acts on, at the positions and velocities an
+/// integrator is trying out, which needn't be where the particles are now
+pub struct Bodies<'a> {
+    pub particles: &'a [Particle],
+    pub positions: &'a [Vector3<f64>],
+    pub velocities: &'a [Vector3<f64>],
+    /// Strands joining the particles. Free bugs have none.
+    pub strands: &'a [SilkStrand],
+}
+
+/// The forces a generator puts on a set of bodies
+pub struct GeneratedForces {
+    /// The force on each body, in the same order
+    pub particles: Vec<Vector3<f64>>,
+    /// The force each strand pulls its start particle with, for generators
+    /// that act through strands. Empty for the rest.
+    pub strands: Vec<Vector3<f64>>,
+}
+
+/// Something that pushes on particles. Generators registered in
+/// `Simulator::force_generators` act on the web and on free bugs alike.
+pub trait ForceGenerator: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The force on each of `bodies`, in the same order
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>>;
+
+    /// The force on each of `bodies` along with the force carried by each
+    /// strand, which decides whether it breaks. Generators that act through
+    /// strands should find both in the same pass.
+    fn generate(&self, sim: &Simulator, bodies: &Bodies) -> GeneratedForces {
+        GeneratedForces { particles: self.forces(sim, bodies), strands: Vec::new() }
+    }
+
+    /// Whether the force is carried by the strands. XPBD solves strands as
+    /// constraints, so it leaves these forces out.
+    fn acts_through_strands(&self) -> bool {
+        false
+    }
+}
+
+/// How much one generator is pushing on everything that can move
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForceContribution {
+    pub name: String,
+    /// Sum of the forces, which is zero for forces that only act between particles
+    pub net_force: Vector3<f64>,
+    /// Sum of the size of each force
+    pub total_magnitude: f64,
+    /// Rate the forces are doing work at, positive when they add energy
+    pub power: f64,
+}
+
+impl ForceContribution {
+    pub fn new(name: &str) -> Self {
+        ForceContribution {
+            name: name.to_string(),
+            net_force: Vector3::zeros(),
+            total_magnitude: 0.0,
+            power: 0.0,
+        }
+    }
+
+    /// Adds the forces on `bodies`, skipping fixed particles
+    pub fn add(&mut self, bodies: &Bodies, forces: &[Vector3<f64>]) {
+        for (i, force) in forces.iter().enumerate() {
+            if bodies.particles[i].fixed {
+                continue;
+            }
+            self.net_force += force;
+            self.total_magnitude += force.norm();
+            self.power += force.dot(&bodies.velocities[i]);
+        }
+    }
+}
+
+/// The simulator's `gravity`, pulling on each particle in proportion to its mass
+pub struct Gravity;
+
+impl ForceGenerator for Gravity {
+    fn name(&self) -> &str {
+        "Gravity"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        bodies.particles.iter().map(|particle| sim.gravity * particle.mass).collect()
+    }
+}
+
+/// Whether the air drags on the particle itself. With the simulator's
+/// `strand_drag` on, silk feels the air through its strands instead.
+fn feels_particle_drag(sim: &Simulator, particle: &Particle) -> bool {
+    !sim.strand_drag || particle.particle_type != ParticleType::Silk
+}
+
+/// Drag in proportion to speed, with the simulator's `drag_coefficient`
+pub struct LinearDrag;
+
+impl ForceGenerator for LinearDrag {
+    fn name(&self) -> &str {
+        "Linear Drag"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        bodies.particles.iter().zip(bodies.velocities)
+            .map(|(particle, velocity)| if feels_particle_drag(sim, particle) { velocity * -sim.drag_coefficient } else { Vector3::zeros() })
+            .collect()
+    }
+}
+
+/// Drag in proportion to the square of speed through the air, as on anything
+/// moving quickly through it. The air moves with the simulator's `wind`.
+pub struct QuadraticDrag {
+    pub coefficient: f64,
+}
+
+impl ForceGenerator for QuadraticDrag {
+    fn name(&self) -> &str {
+        "Quadratic Drag"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        map_range(sim.parallel, bodies.positions.len(), |i| {
+            if !feels_particle_drag(sim, &bodies.particles[i]) {
+                return Vector3::zeros();
+            }
+            let relative = bodies.velocities[i] - sim.wind.velocity(bodies.positions[i], sim.sim_time);
+            relative * (-self.coefficient * relative.norm())
+        })
+    }
+}
+
+/// The simulator's `wind`, dragging each particle along with the air where
+/// it is. Together with `LinearDrag` this is drag on the particle's velocity
+/// relative to the air, so the wind pushes with the same `drag_coefficient`
+/// and does nothing without drag.
+pub struct Wind;
+
+impl ForceGenerator for Wind {
+    fn name(&self) -> &str {
+        "Wind"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        map_range(sim.parallel, bodies.positions.len(), |i| {
+            if !feels_particle_drag(sim, &bodies.particles[i]) {
+                return Vector3::zeros();
+            }
+            sim.wind.velocity(bodies.positions[i], sim.sim_time) * sim.drag_coefficient
+        })
+    }
+}
+
+/// Drag of the air on every strand as a thin cylinder. Only air flowing
+/// across a strand drags on it, so strands side on to the wind catch it while
+/// strands along it barely feel it. Each strand's drag is shared equally
+/// between its two particles.
+///
+/// Only acts while the simulator's `strand_drag` is on, when it takes the
+/// place of `LinearDrag` and `Wind` on silk. The defaults are in the
+/// simulation's own units, chosen so the web feels about as much drag as it
+/// does from those.
+pub struct StrandDrag {
+    pub air_density: f64,
+    pub air_viscosity: f64,
+    /// Thickness of the silk
+    pub diameter: f64,
+}
+
+impl Default for StrandDrag {
+    fn default() -> Self {
+        StrandDrag {
+            air_density: 1.0,
+            air_viscosity: 1.0,
+            diameter: 0.01,
+        }
+    }
+}
+
+impl StrandDrag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drag coefficient of a long cylinder side on to a flow with the given
+    /// Reynolds number. Lamb's solution for slow, viscous flow covers the
+    /// tiny Reynolds numbers of real silk, and White's fit covers faster flow.
+    pub fn drag_coefficient(reynolds: f64) -> f64 {
+        if reynolds < 1.0 {
+            8.0 * std::f64::consts::PI / (reynolds * (2.002 - reynolds.ln()))
+        } else {
+            1.0 + 10.0 * reynolds.powf(-2.0 / 3.0)
+        }
+    }
+
+    /// Drag on a whole strand, given its direction and length and the
+    /// velocity of the air relative to it
+    pub fn strand_force(&self, direction: Vector3<f64>, length: f64, relative_air: Vector3<f64>) -> Vector3<f64> {
+        let across = relative_air - direction * relative_air.dot(&direction);
+        let speed = across.norm();
+        let reynolds = self.air_density * speed * self.diameter / self.air_viscosity;
+        if reynolds <= 0.0 {
+            return Vector3::zeros();
+        }
+        across * (0.5 * self.air_density * speed * Self::drag_coefficient(reynolds) * self.diameter * length)
+    }
+}
+
+impl ForceGenerator for StrandDrag {
+    fn name(&self) -> &str {
+        "Strand Drag"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        if !sim.strand_drag {
+            return vec![Vector3::zeros(); bodies.positions.len()];
+        }
+        let strand_forces = map_range(sim.parallel, bodies.strands.len(), |i| {
+            let silk_strand = &bodies.strands[i];
+            let (start, end) = (bodies.positions[silk_strand.start], bodies.positions[silk_strand.end]);
+            let length = (end - start).norm();
+            if length <= 1e-12 {
+                return Vector3::zeros();
+            }
+            let direction = (end - start) / length;
+            let middle = (start + end) * 0.5;
+            let velocity = (bodies.velocities[silk_strand.start] + bodies.velocities[silk_strand.end]) * 0.5;
+            self.strand_force(direction, length, sim.wind.velocity(middle, sim.sim_time) - velocity)
+        });
+
+        let mut forces = vec![Vector3::zeros(); bodies.positions.len()];
+        for (silk_strand, force) in bodies.strands.iter().zip(strand_forces) {
+            forces[silk_strand.start] += force * 0.5;
+            forces[silk_strand.end] += force * 0.5;
+        }
+        forces
+    }
+}
+
+/// Every strand pulling its two particles together like a damped spring
+pub struct Springs;
+
+impl ForceGenerator for Springs {
+    fn name(&self) -> &str {
+        "Springs"
+    }
+
+    fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+        self.generate(sim, bodies).particles
+    }
+
+    /// Adds up the force of every strand onto both of its particles in a single
+    /// pass over the strands, so it costs O(strands) rather than
+    /// O(particles * strands)
+    fn generate(&self, sim: &Simulator, bodies: &Bodies) -> GeneratedForces {
+        let strand_forces = strand_forces(sim.parallel, bodies.strands, bodies.positions, bodies.velocities);
+
+        // Summed in strand order whether or not the forces were found in
+        // parallel, so both give exactly the same result
+        let mut forces = vec![Vector3::zeros(); bodies.positions.len()];
+        for (silk_strand, force) in bodies.strands.iter().zip(&strand_forces) {
+            // The spring pulls on both ends equally in opposite directions
+            forces[silk_strand.start] += force;
+            forces[silk_strand.end] -= force;
+        }
+        GeneratedForces { particles: forces, strands: strand_forces }
+    }
+
+    fn acts_through_strands(&self) -> bool {
+        true
+    }
+}
+
+/// The generators a new simulator starts with
+pub fn default_generators() -> Vec<Box<dyn ForceGenerator>> {
+    vec![Box::new(Gravity), Box::new(LinearDrag), Box::new(Wind), Box::new(StrandDrag::new()), Box::new(Springs)]
+}
+
+/// Force on the start of a strand, given the position and velocity of its
+/// start relative to its end
+pub fn spring_force(
+    pos_diff: Vector3<f64>,
+    vel_diff: Vector3<f64>,
+    silk_strand: &SilkStrand,
+) -> Vector3<f64> {
+    let pos_diff_len = pos_diff.norm().max(1e-9);
+    let spring_force = pos_diff * (-silk_strand.tension(pos_diff_len) / pos_diff_len);
+    let damp_force = pos_diff * (-silk_strand.damping * vel_diff.dot(&pos_diff) / (pos_diff_len * pos_diff_len));
+
+    spring_force + damp_force
+}
+
+/// Force of every strand on its start particle
+pub fn strand_forces(
+    parallel: bool,
+    strands: &[SilkStrand],
+    positions: &[Vector3<f64>],
+    velocities: &[Vector3<f64>],
+) -> Vec<Vector3<f64>> {
+    map_range(parallel, strands.len(), |i| {
+        let silk_strand = &strands[i];
+        spring_force(
+            positions[silk_strand.start] - positions[silk_strand.end],
+            velocities[silk_strand.start] - velocities[silk_strand.end],
+            silk_strand,
+        )
+    })
+}
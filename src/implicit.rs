@@ -0,0 +1,162 @@
+use nalgebra::{Matrix3, Vector3};
+
+use crate::web::Spiderweb;
+
+/// Linearised backward Euler for the web's springs. Each step solves
+///
+/// (M + h * D + h^2 * K) dv = h * (f - h * K * v)
+///
+/// for the change in velocity, where K and D are the stiffness and damping
+/// Jacobians of the strands. The system is solved with a Jacobi preconditioned
+/// conjugate gradient so stiff silk stays stable at large timesteps.
+#[derive(Copy, Clone, Debug)]
+pub struct ImplicitSolver {
+    /// Most conjugate gradient iterations to run per step
+    pub max_iterations: usize,
+    /// Relative residual at which conjugate gradient stops early
+    pub tolerance: f64,
+}
+
+/// The spring Jacobian stored as one 3x3 block per strand. A strand from `i`
+/// to `j` with block `B` contributes `B` to (i, i) and (j, j) and `-B` to
+/// (i, j) and (j, i), so the blocks are all that's needed for a product.
+struct StrandJacobian {
+    stiffness: Vec<Matrix3<f64>>,
+    damping: Vec<Matrix3<f64>>,
+}
+
+impl StrandJacobian {
+    fn new(web: &Spiderweb) -> Self {
+        let mut stiffness = Vec::with_capacity(web.strands.len());
+        let mut damping = Vec::with_capacity(web.strands.len());
+        for strand in &web.strands {
+            let pos_diff = web.particles[strand.start].position - web.particles[strand.end].position;
+            let len = pos_diff.norm().max(1e-9);
+            let dir = pos_diff / len;
+            let outer = dir * dir.transpose();
+
+            // Dropping the transverse term of compressed strands keeps the
+            // matrix positive definite, which conjugate gradient relies on
+            let transverse = (strand.tension(len) / len).max(0.0);
+            stiffness.push(outer * strand.tangent_stiffness(len) + (Matrix3::identity() - outer) * transverse);
+            damping.push(outer * strand.damping);
+        }
+        StrandJacobian { stiffness, damping }
+    }
+
+    /// Multiplies the assembled matrix with the blocks `a * stiffness + b * damping` by `x`
+    fn multiply(&self, web: &Spiderweb, x: &[Vector3<f64>], a: f64, b: f64) -> Vec<Vector3<f64>> {
+        let mut result = vec![Vector3::zeros(); x.len()];
+        for (i, strand) in web.strands.iter().enumerate() {
+            let block = self.stiffness[i] * a + self.damping[i] * b;
+            let diff = block * (x[strand.start] - x[strand.end]);
+            result[strand.start] += diff;
+            result[strand.end] -= diff;
+        }
+        result
+    }
+}
+
+fn dot(a: &[Vector3<f64>], b: &[Vector3<f64>]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a.dot(b)).sum()
+}
+
+impl Default for ImplicitSolver {
+    fn default() -> Self {
+        ImplicitSolver {
+            max_iterations: 100,
+            tolerance: 1e-6,
+        }
+    }
+}
+
+impl ImplicitSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advances the web by one timestep and returns the new positions and
+    /// velocities of its particles. Fixed particles keep their current state.
+    ///
+    /// * `forces`: The total force on every particle at the start of the step
+    pub fn step(&self, web: &Spiderweb, forces: &[Vector3<f64>], timestep: f64) -> (Vec<Vector3<f64>>, Vec<Vector3<f64>>) {
+        let h = timestep;
+        let jacobian = StrandJacobian::new(web);
+        let velocities: Vec<Vector3<f64>> = web.particles.iter().map(|p| p.velocity).collect();
+
+        // Fixed particles are filtered out of the system so their velocity never changes
+        let filter = |v: &mut Vec<Vector3<f64>>| {
+            for (i, particle) in web.particles.iter().enumerate() {
+                if particle.fixed {
+                    v[i] = Vector3::zeros();
+                }
+            }
+        };
+        let system = |x: &[Vector3<f64>]| -> Vec<Vector3<f64>> {
+            let mut result = jacobian.multiply(web, x, h * h, h);
+            for (i, particle) in web.particles.iter().enumerate() {
+                result[i] += x[i] * particle.mass;
+            }
+            filter(&mut result);
+            result
+        };
+
+        let stiffness_times_velocity = jacobian.multiply(web, &velocities, 1.0, 0.0);
+        let mut rhs: Vec<Vector3<f64>> = forces.iter().zip(&stiffness_times_velocity)
+            .map(|(f, kv)| (f - kv * h) * h)
+            .collect();
+        filter(&mut rhs);
+
+        // Jacobi preconditioner from the diagonal of the system
+        let mut diagonal: Vec<Vector3<f64>> = web.particles.iter().map(|p| Vector3::repeat(p.mass)).collect();
+        for (i, strand) in web.strands.iter().enumerate() {
+            let block = jacobian.stiffness[i] * (h * h) + jacobian.damping[i] * h;
+            let block_diagonal = block.diagonal();
+            diagonal[strand.start] += block_diagonal;
+            diagonal[strand.end] += block_diagonal;
+        }
+        let precondition = |r: &[Vector3<f64>]| -> Vec<Vector3<f64>> {
+            r.iter().zip(&diagonal).map(|(r, d)| r.component_div(d)).collect()
+        };
+
+        let mut dv = vec![Vector3::zeros(); velocities.len()];
+        let mut residual = rhs.clone();
+        let mut z = precondition(&residual);
+        let mut direction = z.clone();
+        let mut rz = dot(&residual, &z);
+        let threshold = self.tolerance * self.tolerance * dot(&rhs, &rhs);
+
+        for _ in 0..self.max_iterations {
+            if dot(&residual, &residual) <= threshold {
+                break;
+            }
+            let a_direction = system(&direction);
+            let alpha = rz / dot(&direction, &a_direction);
+            for i in 0..dv.len() {
+                dv[i] += direction[i] * alpha;
+                residual[i] -= a_direction[i] * alpha;
+            }
+            z = precondition(&residual);
+            let new_rz = dot(&residual, &z);
+            let beta = new_rz / rz;
+            rz = new_rz;
+            for i in 0..direction.len() {
+                direction[i] = z[i] + direction[i] * beta;
+            }
+        }
+
+        let mut new_positions = Vec::with_capacity(dv.len());
+        let mut new_velocities = Vec::with_capacity(dv.len());
+        for (i, particle) in web.particles.iter().enumerate() {
+            if particle.fixed {
+                new_positions.push(particle.position);
+                new_velocities.push(particle.velocity);
+                continue;
+            }
+            let new_velocity = particle.velocity + dv[i];
+            new_positions.push(particle.position + new_velocity * h);
+            new_velocities.push(new_velocity);
+        }
+        (new_positions, new_velocities)
+    }
+}
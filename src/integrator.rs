@@ -0,0 +1,133 @@
+use nalgebra::Vector3;
+
+use crate::parallel::map_range;
+use crate::web::Particle;
+
+/// The numerical scheme used to advance particles by one timestep
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Integrator {
+    /// Störmer-Verlet, using the particle's previous position in place of its velocity
+    PositionVerlet,
+    /// Verlet with explicit velocities, averaging the forces at both ends of the step
+    VelocityVerlet,
+    /// Euler that updates the velocity first and moves with the new velocity
+    SemiImplicitEuler,
+    /// Classic fourth order Runge-Kutta
+    Rk4,
+    /// Linearised implicit Euler, stable for stiff silk. The web's springs are
+    /// solved by `ImplicitSolver`; anything else is stepped with semi-implicit
+    /// Euler, which is what backward Euler becomes without a force Jacobian.
+    BackwardEuler,
+    /// Extended position based dynamics, treating strands as distance
+    /// constraints solved by `XpbdSolver`. Without constraints this is
+    /// semi-implicit Euler.
+    Xpbd,
+}
+
+/// Returns `base + delta * scale` for every element
+fn offset(parallel: bool, base: &[Vector3<f64>], delta: &[Vector3<f64>], scale: f64) -> Vec<Vector3<f64>> {
+    map_range(parallel, base.len(), |i| base[i] + delta[i] * scale)
+}
+
+impl Integrator {
+    /// Every integrator, in the order they are listed in the UI
+    pub const ALL: [Integrator; 6] = [
+        Integrator::PositionVerlet,
+        Integrator::VelocityVerlet,
+        Integrator::SemiImplicitEuler,
+        Integrator::Rk4,
+        Integrator::BackwardEuler,
+        Integrator::Xpbd,
+    ];
+
+    /// Advances `particles` by one timestep and returns their new positions and
+    /// velocities. Fixed particles keep their current state.
+    ///
+    /// * `forces`: Gives the total force on every particle for a set of
+    ///   positions and velocities. It is always called with the state at the
+    ///   start of the step first.
+    /// * `parallel`: Whether to spread the per particle work across threads
+    pub fn integrate<F>(&self, particles: &[Particle], timestep: f64, parallel: bool, mut forces: F) -> (Vec<Vector3<f64>>, Vec<Vector3<f64>>)
+    where
+        F: FnMut(&[Vector3<f64>], &[Vector3<f64>]) -> Vec<Vector3<f64>>,
+    {
+        let dt = timestep;
+        let n = particles.len();
+        let positions: Vec<Vector3<f64>> = particles.iter().map(|p| p.position).collect();
+        let velocities: Vec<Vector3<f64>> = particles.iter().map(|p| p.velocity).collect();
+
+        let mut accelerations = |positions: &[Vector3<f64>], velocities: &[Vector3<f64>]| -> Vec<Vector3<f64>> {
+            let total_forces = forces(positions, velocities);
+            map_range(parallel, n, |i| {
+                if particles[i].fixed { Vector3::zeros() } else { total_forces[i] / particles[i].mass }
+            })
+        };
+
+        let (mut new_positions, mut new_velocities) = match self {
+            Integrator::PositionVerlet => {
+                let a = accelerations(&positions, &velocities);
+                let new_positions = map_range(parallel, n, |i| {
+                    2.0 * particles[i].position - particles[i].prev_position + a[i] * dt * dt
+                });
+                let new_velocities = map_range(parallel, n, |i| {
+                    (new_positions[i] - particles[i].prev_position) / (2.0 * dt)
+                });
+                (new_positions, new_velocities)
+            }
+            Integrator::VelocityVerlet => {
+                let a = accelerations(&positions, &velocities);
+                let new_positions = offset(parallel, &offset(parallel, &positions, &velocities, dt), &a, 0.5 * dt * dt);
+                // Forces depending on velocity (drag, damping) see an Euler estimate
+                let predicted_velocities = offset(parallel, &velocities, &a, dt);
+                let new_a = accelerations(&new_positions, &predicted_velocities);
+                let new_velocities = offset(parallel, &offset(parallel, &velocities, &a, 0.5 * dt), &new_a, 0.5 * dt);
+                (new_positions, new_velocities)
+            }
+            Integrator::SemiImplicitEuler | Integrator::BackwardEuler | Integrator::Xpbd => {
+                let a = accelerations(&positions, &velocities);
+                let new_velocities = offset(parallel, &velocities, &a, dt);
+                let new_positions = offset(parallel, &positions, &new_velocities, dt);
+                (new_positions, new_velocities)
+            }
+            Integrator::Rk4 => {
+                let k1_v = accelerations(&positions, &velocities);
+                let k1_x = velocities.clone();
+
+                let k2_x = offset(parallel, &velocities, &k1_v, 0.5 * dt);
+                let k2_v = accelerations(&offset(parallel, &positions, &k1_x, 0.5 * dt), &k2_x);
+
+                let k3_x = offset(parallel, &velocities, &k2_v, 0.5 * dt);
+                let k3_v = accelerations(&offset(parallel, &positions, &k2_x, 0.5 * dt), &k3_x);
+
+                let k4_x = offset(parallel, &velocities, &k3_v, dt);
+                let k4_v = accelerations(&offset(parallel, &positions, &k3_x, dt), &k4_x);
+
+                let weighted_sum = |k1: &[Vector3<f64>], k2: &[Vector3<f64>], k3: &[Vector3<f64>], k4: &[Vector3<f64>]| -> Vec<Vector3<f64>> {
+                    map_range(parallel, n, |i| (k1[i] + 2.0 * k2[i] + 2.0 * k3[i] + k4[i]) / 6.0)
+                };
+                let new_positions = offset(parallel, &positions, &weighted_sum(&k1_x, &k2_x, &k3_x, &k4_x), dt);
+                let new_velocities = offset(parallel, &velocities, &weighted_sum(&k1_v, &k2_v, &k3_v, &k4_v), dt);
+                (new_positions, new_velocities)
+            }
+        };
+
+        for (i, particle) in particles.iter().enumerate() {
+            if particle.fixed {
+                new_positions[i] = particle.position;
+                new_velocities[i] = particle.velocity;
+            }
+        }
+        (new_positions, new_velocities)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::PositionVerlet => "Position Verlet",
+            Integrator::VelocityVerlet => "Velocity Verlet",
+            Integrator::SemiImplicitEuler => "Semi-Implicit Euler",
+            Integrator::Rk4 => "RK4",
+            Integrator::BackwardEuler => "Backward Euler",
+            Integrator::Xpbd => "XPBD",
+        }
+    }
+}
@@ -1,18 +1,38 @@
 extern crate glfw;
+use std::borrow::Cow;
 use std::sync::mpsc::Receiver;
 
-use imgui::{self, im_str};
+use imgui::{self, im_str, ImStr, ImString};
+use bug::{Behaviour, BugSpecies};
+use emitter::{read_spawn_script, Emitter, EmitterKind};
+use events::SimEvent;
+use integrator::Integrator;
+use material::SilkMaterial;
 use nalgebra::Vector3;
 use renderer::Renderer;
-use simulator::Simulator;
+use simulator::{Attachment, Simulator};
 use rand::Rng;
 use glfw::{Action, Context, Key, Window};
 use webgen::Webgen;
-
+use wind::{read_wind_log, ConstantWind, GustyWind, LoopyWind, RecordedWind, ShearWind, SwayingWind, TurbulentWind, WindField};
+
+pub mod adaptive;
+pub mod bug;
+pub mod collision;
+pub mod emitter;
+pub mod events;
+pub mod forces;
+pub mod implicit;
+pub mod integrator;
+pub mod material;
+pub mod parallel;
 pub mod renderer;
 pub mod simulator;
+pub mod spatial;
 pub mod web;
 pub mod webgen;
+pub mod wind;
+pub mod xpbd;
 
 
 pub fn open_window(glfw: &mut glfw::Glfw) -> (Window, Receiver<(f64, glfw::WindowEvent)>) {
@@ -36,13 +56,80 @@ pub fn open_window(glfw: &mut glfw::Glfw) -> (Window, Receiver<(f64, glfw::Windo
 
 }
 
-fn add_bug(simulator: &mut Simulator) {
+fn integrator_label(integrator: &Integrator) -> Cow<'_, ImStr> {
+    Cow::Owned(ImString::new(integrator.name()))
+}
+
+const BUG_BEHAVIOURS: [&str; 3] = ["Straight", "Wander", "Seek Light"];
+const EMITTER_KINDS: [&str; 4] = ["Point", "Curtain", "Swarm", "Scripted"];
+const WIND_KINDS: [&str; 7] = ["Swaying", "Loopy", "Constant", "Shear", "Turbulent", "Gusty", "Recorded"];
+
+fn silk_label(material: &SilkMaterial) -> Cow<'_, ImStr> {
+    Cow::Owned(ImString::new(material.name))
+}
+
+fn str_label<'a>(name: &'a &'static str) -> Cow<'a, ImStr> {
+    Cow::Owned(ImString::new(*name))
+}
+
+fn bug_behaviour(behaviour_idx: usize, rnd: &mut impl Rng) -> Behaviour {
+    match behaviour_idx {
+        1 => Behaviour::wander(rnd, 2.0),
+        // A light in the middle of the web
+        2 => Behaviour::SeekLight { light: Vector3::zeros() },
+        _ => Behaviour::Straight,
+    }
+}
+
+/// An emitter of the given kind a little in front of the web, aimed at it.
+/// Scripted emitters read their spawns from `script_path`.
+fn make_emitter(kind_idx: usize, rate: f64, script_path: &str) -> Result<EmitterKind, Box<dyn std::error::Error>> {
+    let towards_web = Vector3::new(0.0, 0.0, -1.0);
+    Ok(match kind_idx {
+        0 => EmitterKind::Point { position: Vector3::new(0.0, 0.0, 1.0), direction: towards_web, spread: 0.3, rate },
+        1 => EmitterKind::Curtain {
+            centre: Vector3::new(0.0, 0.0, 1.0),
+            half_width: Vector3::new(0.5, 0.0, 0.0),
+            half_height: Vector3::new(0.0, 0.5, 0.0),
+            direction: towards_web,
+            rate,
+        },
+        2 => EmitterKind::Swarm { centre: Vector3::new(0.0, 0.0, 1.5), radius: 0.3, target: Vector3::zeros(), rate },
+        _ => EmitterKind::Scripted { spawns: read_spawn_script(script_path)? },
+    })
+}
+
+/// Wind of the given kind, blowing across the web at about `speed`.
+/// Recorded wind is read from `log_path` instead, and with frozen turbulence
+/// it's taken to have been recorded at `recorded_at`.
+fn make_wind(kind_idx: usize, speed: f64, log_path: &str, looping: bool, frozen_turbulence: bool, recorded_at: Vector3<f64>) -> Result<Box<dyn WindField>, Box<dyn std::error::Error>> {
+    let across_web = Vector3::new(speed, 0.0, 0.0);
+    let seed = rand::thread_rng().gen();
+    Ok(match kind_idx {
+        1 => Box::new(LoopyWind { strength: speed }),
+        2 => Box::new(ConstantWind::new(across_web)),
+        3 => Box::new(ShearWind { velocity: across_web, reference_height: 1.0, exponent: 0.14 }),
+        4 => Box::new(TurbulentWind::new(across_web * 0.5, speed, 0.2, 2.0, seed)),
+        5 => Box::new(GustyWind::new(across_web * 0.5, 0.5, 1.0, speed, seed)),
+        6 => {
+            let mut wind = RecordedWind::new(read_wind_log(log_path)?);
+            wind.looping = looping;
+            wind.frozen_turbulence = frozen_turbulence.then_some(recorded_at);
+            Box::new(wind)
+        }
+        _ => Box::new(SwayingWind { strength: speed }),
+    })
+}
+
+fn add_bug(simulator: &mut Simulator, species: &BugSpecies, behaviour_idx: usize, lifespan: f64) {
     let mut rnd = rand::thread_rng();
     let particles = &simulator.get_web().particles;
     let rand_pos = Vector3::new(rnd.gen_range(-1.0..1.0), rnd.gen_range(-1.0..1.0), rnd.gen_range(-1.0..1.0));
     let rand_web_particle = particles[rnd.gen_range(0..particles.len())];
-    let velocity = (rand_web_particle.position - rand_pos).normalize() * 1.0;
-    simulator.add_bug(rand_pos, velocity, 2.0);
+    let mut bug = species.spawn(&mut rnd, rand_pos, rand_web_particle.position - rand_pos);
+    bug.particle.lifespan = lifespan;
+    bug.behaviour = bug_behaviour(behaviour_idx, &mut rnd);
+    simulator.spawn_bug(bug);
 }
 
 fn fps_test(simulator: &Simulator) {
@@ -88,7 +175,7 @@ fn fps_bug_test(simulator: &mut Simulator) {
         let mut sim = Simulator::new(simulator.timestep, web);
         let step_count = 5;
         for _ in 0..total_bugs {
-            add_bug(&mut sim);
+            add_bug(&mut sim, &BugSpecies::MOTH, 0, f64::INFINITY);
         }
         let cur_time = std::time::Instant::now();
         for _ in 0..step_count {
@@ -138,6 +225,28 @@ fn main() {
     imgui.io_mut().display_framebuffer_scale = [scale_x, scale_y];
 
     let mut started = false;
+    let mut rollbacks = 0;
+    let mut unresolved = 0;
+    let mut bugs_caught = 0;
+    let mut last_impact_energy = 0.0;
+    let mut bug_behaviour_idx = 0;
+    let mut bugs_escaped = 0;
+    let mut bugs_expired = 0;
+    let mut bug_lifespan: f32 = 10.0;
+    let mut bug_species_idx = 2;
+    let mut emitter_kind_idx = 0;
+    let mut emitter_rate: f32 = 1.0;
+    let mut spawn_script = ImString::with_capacity(256);
+    spawn_script.push_str("spawns.csv");
+    let mut total_time_to_escape = 0.0;
+    let mut wind_kind_idx = 0;
+    let mut wind_speed: f32 = 0.1;
+    let mut wind_log = ImString::with_capacity(256);
+    wind_log.push_str("wind.csv");
+    let mut loop_wind_log = true;
+    let mut frozen_turbulence = false;
+    let mut radial_silk_idx = 1;
+    let mut spiral_silk_idx = 2;
 
     while !window.should_close() {
         glfw.poll_events();
@@ -167,12 +276,21 @@ fn main() {
                 // of spaces to the right of the text element (invisible space)
                 ui.text(im_str!("### Simulation Controls ###\n"));
                 ui.checkbox(im_str!("Simulation Running"), &mut started);
+                let mut wind_changed = false;
                 if ui.button(im_str!("Reset"), [100.0, 20.0]) {
                     started = false;
                     simulator = Simulator::new(timestep, webgen.realistic_web());
+                    wind_changed = true;
+                    rollbacks = 0;
+                    unresolved = 0;
+                    bugs_caught = 0;
+                    last_impact_energy = 0.0;
+                    bugs_escaped = 0;
+                    bugs_expired = 0;
+                    total_time_to_escape = 0.0;
                 }
                 if ui.button(im_str!("Add Bug"), [100.0, 20.0]) {
-                    add_bug(&mut simulator);
+                    add_bug(&mut simulator, &BugSpecies::PRESETS[bug_species_idx], bug_behaviour_idx, bug_lifespan as f64);
                 }
 
                 ui.text(im_str!("\n## Simulation Parameters ##\n"));
@@ -182,18 +300,124 @@ fn main() {
                 ui.input_float(im_str!(" "), &mut gravity).build();
                 simulator.gravity.y = gravity as f64;
 
-                // Wind Strength
-                let mut wind_strength = simulator.wind_strength as f32;
-                ui.text(im_str!("Wind Strength"));
-                ui.input_float(im_str!("  "), &mut wind_strength).build();
-                simulator.wind_strength = wind_strength as f64;
+                // Wind
+                ui.text(im_str!("Wind"));
+                wind_changed |= imgui::ComboBox::new(im_str!("                    ")).build_simple(&ui, &mut wind_kind_idx, &WIND_KINDS, &str_label);
+                if WIND_KINDS[wind_kind_idx] == "Recorded" {
+                    ui.text(im_str!("Wind Log"));
+                    ui.input_text(im_str!("                     "), &mut wind_log).build();
+                    wind_changed |= ui.checkbox(im_str!("Loop Recording"), &mut loop_wind_log);
+                    wind_changed |= ui.checkbox(im_str!("Frozen Turbulence"), &mut frozen_turbulence);
+                    wind_changed |= ui.button(im_str!("Load Wind Log"), [100.0, 20.0]);
+                } else {
+                    // Wind Speed
+                    ui.text(im_str!("Wind Speed"));
+                    wind_changed |= ui.input_float(im_str!("  "), &mut wind_speed).build();
+                    if ui.is_item_hovered() {
+                        ui.tooltip_text("How fast the air moves. It pushes through drag, so with no drag it does nothing.");
+                    }
+                }
+                if wind_changed {
+                    // Wind logs are taken to have been recorded in the middle of the web
+                    let recorded_at = simulator.web().centre();
+                    match make_wind(wind_kind_idx, wind_speed as f64, wind_log.to_str(), loop_wind_log, frozen_turbulence, recorded_at) {
+                        Ok(wind) => simulator.wind = wind,
+                        Err(err) => println!("Couldn't read wind log {}: {}", wind_log.to_str(), err),
+                    }
+                }
 
                 // Drag Coefficient
                 let mut drag_coefficient = simulator.drag_coefficient as f32;
                 ui.text(im_str!("Drag Coefficient"));
                 ui.input_float(im_str!("   "), &mut drag_coefficient).build();
+                if ui.is_item_hovered() {
+                    ui.tooltip_text("Drag on the velocity relative to the air, so this is also how hard the wind pushes.");
+                }
                 simulator.drag_coefficient = drag_coefficient as f64;
 
+                // Strand Drag
+                ui.checkbox(im_str!("Strand Drag"), &mut simulator.strand_drag);
+
+                // Bug Thrust
+                let mut bug_thrust = simulator.bug_thrust as f32;
+                ui.text(im_str!("Bug Thrust"));
+                ui.input_float(im_str!("           "), &mut bug_thrust).build();
+                simulator.bug_thrust = bug_thrust as f64;
+
+                // Bug Restitution
+                let mut bug_restitution = simulator.bug_restitution as f32;
+                ui.text(im_str!("Bug Restitution"));
+                ui.input_float(im_str!("                   "), &mut bug_restitution).build();
+                simulator.bug_restitution = (bug_restitution as f64).clamp(0.0, 1.0);
+
+                // Bug Behaviour
+                ui.text(im_str!("Bug Behaviour"));
+                imgui::ComboBox::new(im_str!("            ")).build_simple(&ui, &mut bug_behaviour_idx, &BUG_BEHAVIOURS, &str_label);
+
+                // Bug Lifespan
+                ui.text(im_str!("Bug Lifespan"));
+                ui.input_float(im_str!("               "), &mut bug_lifespan).build();
+
+                // Strand Visibility
+                let mut strand_visibility = simulator.senses.strand_visibility as f32;
+                ui.text(im_str!("Strand Visibility"));
+                ui.input_float(im_str!("             "), &mut strand_visibility).build();
+                simulator.senses.strand_visibility = strand_visibility.clamp(0.0, 1.0) as f64;
+
+                // Bug Species
+                ui.text(im_str!("Bug Species"));
+                imgui::ComboBox::new(im_str!("              ")).build_simple(&ui, &mut bug_species_idx, &BugSpecies::PRESETS, &|species: &BugSpecies| Cow::Owned(ImString::new(species.name)));
+
+                ui.text(im_str!("\n######### Emitters ########\n"));
+                ui.text(im_str!("Emitter Kind"));
+                imgui::ComboBox::new(im_str!("                ")).build_simple(&ui, &mut emitter_kind_idx, &EMITTER_KINDS, &str_label);
+                if EMITTER_KINDS[emitter_kind_idx] == "Scripted" {
+                    ui.text(im_str!("Spawn Script"));
+                    ui.input_text(im_str!("                 "), &mut spawn_script).build();
+                } else {
+                    ui.text(im_str!("Emitter Rate"));
+                    ui.input_float(im_str!("                  "), &mut emitter_rate).build();
+                }
+                // New emitters release bugs of the species, behaviour and lifespan chosen above
+                if ui.button(im_str!("Add Emitter"), [100.0, 20.0]) {
+                    match make_emitter(emitter_kind_idx, emitter_rate as f64, spawn_script.to_str()) {
+                        Ok(kind) => {
+                            let mut emitter = Emitter::new(kind, BugSpecies::PRESETS[bug_species_idx]);
+                            emitter.behaviour = bug_behaviour(bug_behaviour_idx, &mut rand::thread_rng());
+                            emitter.lifespan = bug_lifespan as f64;
+                            simulator.emitters.push(emitter);
+                        }
+                        Err(err) => println!("Couldn't read spawn script {}: {}", spawn_script.to_str(), err),
+                    }
+                }
+                if ui.button(im_str!("Clear Emitters"), [100.0, 20.0]) {
+                    simulator.emitters.clear();
+                }
+                for emitter in &simulator.emitters {
+                    ui.text(im_str!("{} emitter of {}", emitter.name(), emitter.species.name));
+                }
+
+                // Integrator
+                let mut integrator_idx = Integrator::ALL.iter().position(|&i| i == simulator.integrator).unwrap_or(0);
+                ui.text(im_str!("Integrator"));
+                if imgui::ComboBox::new(im_str!("         ")).build_simple(&ui, &mut integrator_idx, &Integrator::ALL, &integrator_label) {
+                    simulator.integrator = Integrator::ALL[integrator_idx];
+                }
+
+                ui.checkbox(im_str!("Adaptive Timestep"), &mut simulator.adaptive.enabled);
+                ui.checkbox(im_str!("Multi-threaded"), &mut simulator.parallel);
+
+                let mut hanging = simulator.attachment == Attachment::Hanging;
+                ui.checkbox(im_str!("Bugs Hang From Web"), &mut hanging);
+                simulator.attachment = if hanging { Attachment::Hanging } else { Attachment::Embedded };
+
+                if simulator.integrator == Integrator::Xpbd {
+                    let mut xpbd_iterations = simulator.xpbd_solver.iterations as i32;
+                    ui.text(im_str!("XPBD Iterations"));
+                    ui.input_int(im_str!("          "), &mut xpbd_iterations).build();
+                    simulator.xpbd_solver.iterations = xpbd_iterations.max(1) as usize;
+                }
+
                 ui.text(im_str!("\n##### Web Generation ######\n"));
 
                 let mut stiffness = webgen.stiffness as f32;
@@ -206,6 +430,14 @@ fn main() {
                 ui.input_float(im_str!("     "), &mut damping).build();
                 webgen.damping = damping as f64;
 
+                ui.text(im_str!("Radial Silk"));
+                imgui::ComboBox::new(im_str!("                      ")).build_simple(&ui, &mut radial_silk_idx, &SilkMaterial::PRESETS, &silk_label);
+                webgen.radial_material = SilkMaterial::PRESETS[radial_silk_idx];
+
+                ui.text(im_str!("Spiral Silk"));
+                imgui::ComboBox::new(im_str!("                       ")).build_simple(&ui, &mut spiral_silk_idx, &SilkMaterial::PRESETS, &silk_label);
+                webgen.spiral_material = SilkMaterial::PRESETS[spiral_silk_idx];
+
                 let mut mass = webgen.mass as f32;
                 ui.text(im_str!("Mass"));
                 ui.input_float(im_str!("      "), &mut mass).build();
@@ -234,9 +466,25 @@ fn main() {
             .position(info_window_pos, imgui::Condition::Always)
             .build(&ui, || {
                 ui.text(im_str!("Timestep: {}", timestep));
+                ui.text(im_str!("Integrator: {}", simulator.integrator.name()));
                 ui.text(im_str!("Strands: {}", simulator.get_web().strands.len()));
                 ui.text(im_str!("Bugs: {}", simulator.bugs.len()));
+                ui.text(im_str!("Bugs Caught: {}", bugs_caught));
+                ui.text(im_str!("Last Impact Energy: {:.3}", last_impact_energy));
+                ui.text(im_str!("Bugs Escaped: {}", bugs_escaped));
+                ui.text(im_str!("Bugs Expired: {}", bugs_expired));
+                if bugs_escaped > 0 {
+                    ui.text(im_str!("Mean Time To Escape: {:.2}", total_time_to_escape / bugs_escaped as f64));
+                }
                 ui.text(im_str!("Simulation Time: {}", simulator.sim_time));
+                for contribution in simulator.force_contributions() {
+                    ui.text(im_str!("{}: {:.3} (power {:.3})", contribution.name, contribution.total_magnitude, contribution.power));
+                }
+                if simulator.adaptive.enabled {
+                    ui.text(im_str!("Substeps: {}", simulator.substeps));
+                    ui.text(im_str!("Rollbacks: {}", rollbacks));
+                    ui.text(im_str!("Unresolved: {}", unresolved));
+                }
                 ui.text(im_str!("Zoom: {:.1}", renderer.zoom / 3.0));
             });
             
@@ -275,6 +523,21 @@ fn main() {
         if started {
             simulator.step();
         }
+        for event in simulator.drain_events() {
+            match event {
+                SimEvent::RolledBack { .. } => rollbacks += 1,
+                SimEvent::Unresolved { .. } => unresolved += 1,
+                SimEvent::BugCaught { impact_energy, .. } => {
+                    bugs_caught += 1;
+                    last_impact_energy = impact_energy;
+                }
+                SimEvent::BugEscaped { time_to_escape, .. } => {
+                    bugs_escaped += 1;
+                    total_time_to_escape += time_to_escape;
+                }
+                SimEvent::BugExpired { .. } => bugs_expired += 1,
+            }
+        }
         window.set_title("Spiderweb Simulator");
         window.swap_buffers();
     }
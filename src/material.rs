@@ -0,0 +1,100 @@
+/// How a kind of silk stretches, as a stress–strain curve through a list of
+/// points joined by straight lines. Stress is relative to the strand's
+/// `stiffness`, so a strand's tension is its stiffness times its rest length
+/// times the stress at its strain. Squashed strands push back as if the first
+/// segment carried on below zero, and strands stretched past the last point
+/// carry on along the last segment until they snap at `failure_strain`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SilkMaterial {
+    pub name: &'static str,
+    /// Points of (strain, stress) in order of strain, starting at (0, 0)
+    pub curve: &'static [(f64, f64)],
+    /// Strain at which a strand of the silk breaks
+    pub failure_strain: f64,
+}
+
+impl SilkMaterial {
+    /// Hooke's law all the way, never breaking however far it's stretched
+    pub const LINEAR: SilkMaterial = SilkMaterial {
+        name: "Linear",
+        curve: &[(0.0, 0.0), (1.0, 1.0)],
+        failure_strain: f64::INFINITY,
+    };
+    /// Dragline silk the frame and radii are made of. Stiff at first, it
+    /// yields after a couple of percent, then stiffens sharply before
+    /// breaking at around a quarter longer than it started.
+    pub const MAJOR_AMPULLATE: SilkMaterial = SilkMaterial {
+        name: "Major Ampullate",
+        curve: &[(0.0, 0.0), (0.02, 0.02), (0.12, 0.035), (0.2, 0.08), (0.27, 0.18)],
+        failure_strain: 0.27,
+    };
+    /// Capture silk the spiral is made of. It yields almost straight away
+    /// and stretches to several times its length, stiffening as it goes, so
+    /// it soaks up the energy of bugs that fly into it.
+    pub const FLAGELLIFORM: SilkMaterial = SilkMaterial {
+        name: "Flagelliform",
+        curve: &[(0.0, 0.0), (0.05, 0.05), (1.0, 0.15), (2.0, 0.6), (2.7, 1.6)],
+        failure_strain: 2.7,
+    };
+    pub const PRESETS: [SilkMaterial; 3] = [Self::LINEAR, Self::MAJOR_AMPULLATE, Self::FLAGELLIFORM];
+
+    /// Index of the segment of the curve `strain` falls on
+    fn segment(&self, strain: f64) -> usize {
+        let segments = self.curve.len() - 1;
+        self.curve[1..segments].partition_point(|&(point_strain, _)| point_strain <= strain)
+    }
+
+    fn slope_of(&self, segment: usize) -> f64 {
+        let ((strain_0, stress_0), (strain_1, stress_1)) = (self.curve[segment], self.curve[segment + 1]);
+        (stress_1 - stress_0) / (strain_1 - strain_0)
+    }
+
+    pub fn stress(&self, strain: f64) -> f64 {
+        let segment = self.segment(strain);
+        let (start_strain, start_stress) = self.curve[segment];
+        start_stress + self.slope_of(segment) * (strain - start_strain)
+    }
+
+    /// How quickly stress rises with strain at `strain`
+    pub fn slope(&self, strain: f64) -> f64 {
+        self.slope_of(self.segment(strain))
+    }
+
+    /// Area under the curve from no strain to `strain`, which is the energy
+    /// stored in the silk
+    pub fn energy(&self, strain: f64) -> f64 {
+        if strain <= 0.0 {
+            return 0.5 * self.slope_of(0) * strain * strain;
+        }
+        let end = self.segment(strain);
+        let full_segments: f64 = self.curve[..=end].windows(2)
+            .map(|pair| 0.5 * (pair[0].1 + pair[1].1) * (pair[1].0 - pair[0].0))
+            .sum();
+        let (start_strain, start_stress) = self.curve[end];
+        full_segments + 0.5 * (start_stress + self.stress(strain)) * (strain - start_strain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stress_passes_through_every_point_of_the_curve() {
+        for silk in SilkMaterial::PRESETS {
+            for &(strain, stress) in silk.curve {
+                assert!((silk.stress(strain) - stress).abs() < 1e-12, "{} at {}", silk.name, strain);
+            }
+        }
+    }
+
+    #[test]
+    fn energy_is_the_area_under_the_curve() {
+        for silk in SilkMaterial::PRESETS {
+            for strain in [-0.1, 0.01, 0.1, 0.15, 0.25, 1.5, 2.5] {
+                let derivative = (silk.energy(strain + 1e-6) - silk.energy(strain - 1e-6)) / 2e-6;
+                assert!((derivative - silk.stress(strain)).abs() < 1e-6, "{} at {}", silk.name, strain);
+            }
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use rayon::prelude::*;
+
+/// Maps `f` over `0..len`, spread across threads when `parallel` is set.
+/// Each element is computed independently and by the same code either way, so
+/// the results are identical bit for bit. Anything that sums across elements
+/// has to happen afterwards, in a fixed order.
+pub fn map_range<T, F>(parallel: bool, len: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    if parallel {
+        (0..len).into_par_iter().map(f).collect()
+    } else {
+        (0..len).map(f).collect()
+    }
+}
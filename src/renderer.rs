@@ -3,7 +3,7 @@ extern crate glfw;
 
 use std::ffi::CString;
 use crate::simulator::Simulator;
-use crate::web::{Particle, ParticleType, Spiderweb};
+use crate::web::{Particle, Spiderweb};
 use std::fs::File;
 use std::io::prelude::*;
 use gl::types::*;
@@ -164,14 +164,14 @@ impl Renderer {
         }
     }
 
-    unsafe fn draw_bug(&self, bug: &Particle) {
+    unsafe fn draw_bug(&self, bug: &Particle, radius: f64, pixels_per_unit: f32) {
         let pos = bug.position;
         let gl_pos = [
             pos.x as GLfloat, pos.y as GLfloat, pos.z as GLfloat,
         ];
         self.set_uniform_color([0.0, 1.0, 0.0, 1.0]);
         
-        gl::PointSize(10.0);
+        gl::PointSize((2.0 * radius as f32 * pixels_per_unit).max(2.0));
         gl::BufferData(gl::ARRAY_BUFFER,
                         (gl_pos.len() * std::mem::size_of::<GLfloat>()) as gl::types::GLsizeiptr,
                         gl_pos.as_ptr() as *const gl::types::GLvoid,
@@ -192,17 +192,16 @@ impl Renderer {
             self.set_uniform_color([1.0, 1.0, 1.0, 1.0]);
             self.draw_line(&vertices, 10.0);
         }
-        for particle in &web.particles {
-            if particle.particle_type != ParticleType::Bug {
-                continue;
-            }
-            self.draw_bug(particle)
-        }
     }
-    // Draw bugs as little green points
-    unsafe fn draw_bugs(&self, bugs: &Vec<Particle>) {
-        for bug in bugs {
-            self.draw_bug(bug)
+    // Draw bugs as green points as big as the bugs, whether they're flying or
+    // stuck in the web
+    unsafe fn draw_bugs(&self, sim: &Simulator, pixels_per_unit: f32) {
+        for bug in &sim.bugs {
+            self.draw_bug(&bug.particle, bug.radius, pixels_per_unit)
+        }
+        for captured in &sim.captured {
+            let particle = &sim.web().particles[captured.particle_idx];
+            self.draw_bug(particle, captured.bug.radius, pixels_per_unit)
         }
     }
 
@@ -252,7 +251,9 @@ impl Renderer {
 
         self.draw_xyz_lines();
         self.draw_web(web);
-        self.draw_bugs(&sim.bugs);
+        // How many pixels across one unit is at the middle of the scene
+        let pixels_per_unit = window.get_size().1 as f32 / (2.0 * dist_from_center * (fov / 2.0).tan());
+        self.draw_bugs(sim, pixels_per_unit);
 
         gl::DisableVertexAttribArray(0);
         gl::BindBuffer(gl::ARRAY_BUFFER, 0);
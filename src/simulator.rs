@@ -1,20 +1,50 @@
 use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
+use crate::adaptive::AdaptiveStepping;
+use crate::bug::{random_unit_vector, Bug, CapturedBug, Senses};
+use crate::collision::{closest_point_on_segment, sweep_sphere_against_segment, Contact, Sweep};
+use crate::emitter::Emitter;
+use crate::events::{Divergence, SimEvent};
+use crate::forces::{default_generators, Bodies, ForceContribution, ForceGenerator, GeneratedForces};
+use crate::implicit::ImplicitSolver;
+use crate::integrator::Integrator;
+use crate::material::SilkMaterial;
+use crate::parallel::map_range;
+use crate::spatial::SpatialGrid;
 use crate::web::{Particle, ParticleType, SilkStrand, Spiderweb};
+use crate::wind::{SwayingWind, WindField};
+use crate::xpbd::XpbdSolver;
 
-fn calculate_spring_force(
-    particle: &Particle,
-    connected_particle: &Particle,
-    silk_strand: &SilkStrand,
-) -> Vector3<f64> {
-    let pos_diff = particle.position - connected_particle.position;
-    let vel_diff = particle.velocity - connected_particle.velocity;
+/// Where a bug's particle lives: free bugs are in `Simulator::bugs`, and
+/// caught ones are part of the web
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BugBody {
+    Free(usize),
+    Captured(usize),
+}
 
-    let pos_diff_len = pos_diff.norm().max(1e-9);
-    let spring_force = pos_diff * (silk_strand.stiffness * (silk_strand.length - pos_diff_len) / pos_diff_len);
-    let damp_force = pos_diff * (-silk_strand.damping * vel_diff.dot(&pos_diff) / (pos_diff_len * pos_diff_len));
+/// How a bug is attached to the strand it hits
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Attachment {
+    /// The bug is pulled onto the strand at the point it hit, becoming part of it
+    Embedded,
+    /// A new point is added on the strand where the bug hit, and the bug hangs
+    /// from it by a short thread
+    Hanging,
+}
 
-    spring_force + damp_force
+/// Everything a substep can change, saved so a substep that diverges can be
+/// rolled back and tried again
+struct Snapshot {
+    web: Spiderweb,
+    bugs: Vec<Bug>,
+    captured: Vec<CapturedBug>,
+    rng: StdRng,
+    sim_time: f64,
+    last_timestep: f64,
+    event_count: usize,
 }
 
 pub struct Simulator {
@@ -22,11 +52,48 @@ pub struct Simulator {
     pub timestep: f64,
     pub sim_time: f64,
     pub gravity: Vector3<f64>,
+    /// Drag on the velocity of particles relative to the air, which is also
+    /// how hard the wind pushes them
     pub drag_coefficient: f64,
-    pub wind_fn: fn(&Self, Vector3<f64>) -> Vector3<f64>,
-    pub bugs: Vec<Particle>,
-    pub wind_strength: f64,
+    /// The air the web sits in, given as its velocity
+    pub wind: Box<dyn WindField>,
+    /// Everything pushing on the web and on free bugs, summed in order
+    pub force_generators: Vec<Box<dyn ForceGenerator>>,
+    /// Drag the air on silk through its strands rather than on each silk
+    /// particle. Bugs feel the air the same either way.
+    pub strand_drag: bool,
+    pub bugs: Vec<Bug>,
+    /// Force free bugs fly the way their behaviour steers them with. With none
+    /// they just drift.
+    pub bug_thrust: f64,
+    pub senses: Senses,
+    /// Sources of new bugs, run at the start of every step
+    pub emitters: Vec<Emitter>,
+    /// Bugs stuck in the web, in the order they were caught
+    pub captured: Vec<CapturedBug>,
+    /// How often a caught bug changes which way it struggles
+    pub struggle_period: f64,
+    /// How much of their closing speed two bugs that bump into each other
+    /// bounce apart with, from 0 to 1
+    pub bug_restitution: f64,
+    /// Force linear strands snap at. Other silk breaks where its curve ends,
+    /// at its failure strain.
     pub max_silk_strand_force: f64,
+    pub attachment: Attachment,
+    pub integrator: Integrator,
+    pub implicit_solver: ImplicitSolver,
+    pub xpbd_solver: XpbdSolver,
+    pub adaptive: AdaptiveStepping,
+    /// Spread forces, integration and collision detection across threads. The
+    /// results are identical to running on a single thread.
+    pub parallel: bool,
+    /// How many substeps the last step was split into
+    pub substeps: usize,
+    last_timestep: f64,
+    events: Vec<SimEvent>,
+    /// Source of everything random in the simulation, so a seeded run can be
+    /// repeated exactly
+    rng: StdRng,
 }
 
 impl Simulator {
@@ -37,154 +104,675 @@ impl Simulator {
             sim_time: 0.0,
             gravity: Vector3::new(0.0, -0.1, 0.0),
             drag_coefficient: 0.5,
-            wind_fn: Self::default_wind_fn,
+            wind: Box::new(SwayingWind { strength: 0.1 }),
+            force_generators: default_generators(),
+            strand_drag: false,
             bugs: Vec::new(),
-            wind_strength: 0.05,
+            bug_thrust: 0.0,
+            senses: Senses::new(),
+            emitters: Vec::new(),
+            captured: Vec::new(),
+            struggle_period: 0.2,
+            bug_restitution: 0.3,
             max_silk_strand_force: 10.0,
+            attachment: Attachment::Embedded,
+            integrator: Integrator::PositionVerlet,
+            implicit_solver: ImplicitSolver::new(),
+            xpbd_solver: XpbdSolver::new(),
+            adaptive: AdaptiveStepping::new(),
+            parallel: true,
+            substeps: 1,
+            last_timestep: timestep,
+            events: Vec::new(),
+            rng: StdRng::from_entropy(),
         }
     }
 
-    /// Simple wind to blow the web around a bit depending on position and sins
-    fn default_wind_fn(&self, particle_pos: Vector3<f64>) -> Vector3<f64> {
-        let wind_dir = Vector3::new(0.8 *(self.sim_time).sin(), 0.05 * (self.sim_time * 0.1).sin(), 0.1 * (self.sim_time * 0.3).sin());
-        wind_dir * (particle_pos.y * self.wind_strength)
-        
+    /// Makes every random choice from here on repeat from run to run
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
     }
 
-    /// Wind that blows in a loop, blowing greater when closer to the z-axis
-    fn loopy_wind(&self, particle_pos: Vector3<f64>) -> Vector3<f64> {
-        let z_pos = particle_pos.z.max(0.1);
-        let wind_dir = Vector3::new(particle_pos.y/z_pos, -particle_pos.x/z_pos, particle_pos.z/4.0);
-        wind_dir * self.wind_strength
+    /// Adds a free bug flying straight, which is removed if it hasn't hit the
+    /// web within `lifespan`
+    pub fn add_bug(&mut self, position: Vector3<f64>, velocity: Vector3<f64>, mass: f64, lifespan: f64) {
+        let mut bug = Bug::new(position, velocity, mass);
+        bug.particle.lifespan = lifespan;
+        self.spawn_bug(bug);
     }
 
-    pub fn add_bug(&mut self, position: Vector3<f64>, velocity: Vector3<f64>, mass: f64) {
-        let bug = Particle::new(position, velocity, mass, false, ParticleType::Bug);
+    pub fn spawn_bug(&mut self, mut bug: Bug) {
+        // Position verlet gets its velocity from the previous position
+        bug.particle.prev_position = bug.particle.position - bug.particle.velocity * self.timestep;
         self.bugs.push(bug);
     }
 
-    fn calculate_verlet(&self, particle: &Particle, total_force: Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
-        let acceleration = total_force / particle.mass;
-        let new_position = 2.0 * particle.position - particle.prev_position + acceleration * self.timestep * self.timestep;
-        let new_velocity = (new_position - particle.prev_position) / (2.0 * self.timestep);
-        (new_position, new_velocity)
-    }
+    /// Gives the thrust to use the rest of this step for the behaviour of a
+    /// bug, steering it away from any strands it can see ahead of it
+    fn steer(&self, bug: &Bug) -> Vector3<f64> {
+        let desired = bug.desired_heading();
+        let senses = &self.senses;
+        if senses.strand_visibility <= 0.0 || senses.sight_range <= 0.0 {
+            return desired;
+        }
 
-    fn update_particle(&self, particle: &Particle, strands_to_remove: &mut Vec<(usize, usize)>) -> (Vector3<f64>, Vector3<f64>) {
-        if particle.fixed {
-            return (particle.position, particle.velocity);
+        let position = bug.particle.position;
+        let mut avoid = Vector3::zeros();
+        for strand_index in self.web.strands_in_box(position.add_scalar(-senses.sight_range), position.add_scalar(senses.sight_range)) {
+            let strand = &self.web.strands[strand_index];
+            let (start, end) = (self.web.particles[strand.start].position, self.web.particles[strand.end].position);
+            let (distance, t) = closest_point_on_segment(position, start, end);
+            let away = position - start.lerp(&end, t);
+            // Strands behind the bug or out of sight don't matter
+            if distance >= senses.sight_range || away.dot(&bug.particle.velocity) > 0.0 {
+                continue;
+            }
+            let closeness = 1.0 - distance / senses.sight_range;
+            avoid += away.try_normalize(1e-9).unwrap_or_else(Vector3::zeros) * closeness;
         }
+        let avoid = avoid * (senses.strand_visibility * senses.avoidance);
+        (desired + avoid).try_normalize(1e-9).unwrap_or_else(Vector3::zeros)
+    }
 
-        // Gravity
-        let mut total_force = self.gravity * particle.mass;
-        // Drag
-        total_force += particle.velocity * (-self.drag_coefficient);
+    /// Updates every free bug's behaviour and decides where it flies this step
+    fn steer_bugs(&mut self, timestep: f64) {
+        // Random choices are made in order, one bug after another, so they
+        // don't depend on how the work is split across threads
+        for bug in &mut self.bugs {
+            bug.update_behaviour(&mut self.rng, timestep);
+        }
+        let headings = map_range(self.parallel, self.bugs.len(), |i| self.steer(&self.bugs[i]));
+        for (bug, heading) in self.bugs.iter_mut().zip(headings) {
+            bug.heading = heading;
+        }
+    }
 
-        for (i, silk_strand) in self.web.strands.iter().enumerate() {
-            let connected_particle_idx = if self.web.particles[silk_strand.start] == *particle {
-                silk_strand.end
-            } else if self.web.particles[silk_strand.end] == *particle {
-                silk_strand.start
-            } else {
-                continue;
-            };
-            let connected_particle = &self.web.particles[connected_particle_idx];
+    /// Whether the strand at `strand_idx` breaks, given how hard it pulls at `positions`
+    fn strand_breaks(&self, strand_idx: usize, force: f64, positions: &[Vector3<f64>]) -> bool {
+        let strand = &self.web.strands[strand_idx];
+        if strand.material == SilkMaterial::LINEAR {
+            return force > self.max_silk_strand_force;
+        }
+        strand.is_overstretched((positions[strand.start] - positions[strand.end]).norm())
+    }
 
-            let force = calculate_spring_force(
-                particle,
-                connected_particle,
-                silk_strand,
-            );
+    /// Linear strands pulling harder than `max_silk_strand_force`, given the
+    /// force each one carries, and other strands stretched past where their
+    /// silk breaks
+    fn overloaded_strands(&self, strand_forces: &[Vector3<f64>], positions: &[Vector3<f64>]) -> Vec<usize> {
+        strand_forces.iter().enumerate()
+            .filter(|(i, force)| self.strand_breaks(*i, force.norm(), positions))
+            .map(|(i, _)| i)
+            .collect()
+    }
 
-            if force.norm() > self.max_silk_strand_force {
-                strands_to_remove.push((i, connected_particle_idx));
+    /// Adds up the forces of the generators on `bodies` and on the strands
+    /// between them, leaving out those carried by strands unless
+    /// `include_strands` is set
+    fn sum_forces(&self, bodies: &Bodies, include_strands: bool) -> GeneratedForces {
+        let mut total = GeneratedForces {
+            particles: vec![Vector3::zeros(); bodies.positions.len()],
+            strands: vec![Vector3::zeros(); bodies.strands.len()],
+        };
+        for generator in self.force_generators.iter().filter(|generator| include_strands || !generator.acts_through_strands()) {
+            let generated = generator.generate(self, bodies);
+            for (total_force, force) in total.particles.iter_mut().zip(generated.particles) {
+                *total_force += force;
+            }
+            for (total_force, force) in total.strands.iter_mut().zip(generated.strands) {
+                *total_force += force;
             }
-            
-            total_force += force;
         }
+        total
+    }
 
-        let wind_force = (self.wind_fn)(&self, particle.position);
-        total_force += wind_force;
+    fn web_bodies<'a>(&'a self, positions: &'a [Vector3<f64>], velocities: &'a [Vector3<f64>]) -> Bodies<'a> {
+        Bodies {
+            particles: &self.web.particles,
+            positions,
+            velocities,
+            strands: &self.web.strands,
+        }
+    }
+
+    /// Force of every generator on every web particle and strand, with the
+    /// struggling of caught bugs on top
+    fn calculate_web_forces(&self, positions: &[Vector3<f64>], velocities: &[Vector3<f64>], include_strands: bool) -> GeneratedForces {
+        let mut forces = self.sum_forces(&self.web_bodies(positions, velocities), include_strands);
+        for captured in &self.captured {
+            forces.particles[captured.particle_idx] += captured.struggle_force();
+        }
+        forces
+    }
 
-        let drag_force = particle.velocity * -self.drag_coefficient;
-        total_force += drag_force;
+    /// Every force on the web particles except those carried by strands
+    fn calculate_external_forces(&self, positions: &[Vector3<f64>], velocities: &[Vector3<f64>]) -> Vec<Vector3<f64>> {
+        self.calculate_web_forces(positions, velocities, false).particles
+    }
 
-        self.calculate_verlet(particle, total_force)
+    /// Every generator's force and thrust on the free bugs
+    fn calculate_bug_forces(&self, particles: &[Particle], positions: &[Vector3<f64>], velocities: &[Vector3<f64>]) -> Vec<Vector3<f64>> {
+        let bodies = Bodies { particles, positions, velocities, strands: &[] };
+        let mut forces = self.sum_forces(&bodies, true).particles;
+        for (force, bug) in forces.iter_mut().zip(&self.bugs) {
+            *force += bug.heading * self.bug_thrust;
+        }
+        forces
     }
 
-    // Stick a bug to a web by replacing a strand of the web with a strand connecting
-    // from one particle to the bug, and from the bug to the other particle.
-    fn stick_to_web(&mut self, bug_index: usize, strand_index: usize) {
-        let mut bug = self.bugs[bug_index];
-        bug.velocity = Vector3::zeros();
-        self.web.insert_particle_into_web(bug, strand_index, true);
+    /// Total force on every web particle for the given positions and
+    /// velocities. Strands that are overloaded are pushed onto
+    /// `strands_to_remove` if given.
+    fn calculate_forces(
+        &self,
+        positions: &[Vector3<f64>],
+        velocities: &[Vector3<f64>],
+        strands_to_remove: Option<&mut Vec<usize>>,
+    ) -> Vec<Vector3<f64>> {
+        let forces = self.calculate_web_forces(positions, velocities, true);
+        if let Some(strands_to_remove) = strands_to_remove {
+            strands_to_remove.extend(self.overloaded_strands(&forces.strands, positions));
+        }
+        forces.particles
+    }
+
+    /// What each force generator is doing to the web and the free bugs as
+    /// they are now, in the order the generators are registered
+    pub fn force_contributions(&self) -> Vec<ForceContribution> {
+        let positions: Vec<Vector3<f64>> = self.web.particles.iter().map(|p| p.position).collect();
+        let velocities: Vec<Vector3<f64>> = self.web.particles.iter().map(|p| p.velocity).collect();
+        let web = self.web_bodies(&positions, &velocities);
+        let bug_particles: Vec<Particle> = self.bugs.iter().map(|bug| bug.particle).collect();
+        let bug_positions: Vec<Vector3<f64>> = bug_particles.iter().map(|p| p.position).collect();
+        let bug_velocities: Vec<Vector3<f64>> = bug_particles.iter().map(|p| p.velocity).collect();
+        let bugs = Bodies { particles: &bug_particles, positions: &bug_positions, velocities: &bug_velocities, strands: &[] };
+
+        self.force_generators.iter().map(|generator| {
+            let mut contribution = ForceContribution::new(generator.name());
+            contribution.add(&web, &generator.forces(self, &web));
+            contribution.add(&bugs, &generator.forces(self, &bugs));
+            contribution
+        }).collect()
+    }
+
+    // Stick a bug to a web by splitting the strand it hit at the point of contact,
+    // and either putting the bug there or hanging it from there.
+    // The bug keeps flying at the speed it hit with, so the web has to absorb
+    // its momentum. Returns the bug's kinetic energy at impact.
+    fn stick_to_web(&mut self, bug_index: usize, strand_index: usize, contact: &Contact, time: f64) -> f64 {
+        let mut bug = self.bugs[bug_index].particle;
+        // Position verlet gets its velocity from the previous position
+        bug.prev_position = bug.position - bug.velocity * self.last_timestep;
+
+        match self.attachment {
+            Attachment::Embedded => {
+                let bug_idx = self.web.insert_particle_at(bug, strand_index, contact.strand_param);
+                self.captured.push(CapturedBug::new(bug_idx, self.bugs[bug_index], time));
+            }
+            Attachment::Hanging => {
+                let strand = self.web.strands[strand_index];
+                let start_particle = self.web.particles[strand.start];
+                let end_particle = self.web.particles[strand.end];
+                let t = contact.strand_param;
+                // The new point on the strand weighs as much as a strand's free end
+                let mut anchor = Particle::new(
+                    start_particle.position.lerp(&end_particle.position, t),
+                    start_particle.velocity.lerp(&end_particle.velocity, t),
+                    (start_particle.mass + end_particle.mass) / 4.0,
+                    false,
+                    ParticleType::Silk,
+                );
+                anchor.prev_position = start_particle.prev_position.lerp(&end_particle.prev_position, t);
+                let anchor_idx = self.web.insert_particle_at(anchor, strand_index, t);
+
+                // The thread starts at rest, so it only pulls once the bug moves away
+                self.web.push_particle(bug);
+                let bug_idx = self.web.particles.len() - 1;
+                let thread_length = (bug.position - self.web.particles[anchor_idx].position).norm();
+                self.web.push_strand(SilkStrand { start: anchor_idx, end: bug_idx, length: thread_length, ..strand });
+                self.captured.push(CapturedBug::new(bug_idx, self.bugs[bug_index], time));
+            }
+        }
+        0.5 * bug.mass * bug.velocity.norm_squared()
     }
 
+    /// Finds the strand the bug hit first while it and the web moved over the
+    /// last step, if any, with ties going to the lowest strand index. Only
+    /// strands the strand grid has near the bug's path are checked.
+    fn find_collision(&self, bug: &Particle, bug_radius: f64) -> Option<(usize, Contact)> {
+        let min = bug.prev_position.inf(&bug.position).add_scalar(-bug_radius);
+        let max = bug.prev_position.sup(&bug.position).add_scalar(bug_radius);
+        self.first_hit_among(bug, bug_radius, self.web.strands_in_box(min, max))
+    }
+
+    /// Which of the given strands the bug hit first over the last step, if
+    /// any, with ties going to the strand that comes first
+    fn first_hit_among(&self, bug: &Particle, bug_radius: f64, strand_indices: impl IntoIterator<Item = usize>) -> Option<(usize, Contact)> {
+        let bug_sweep = Sweep::of(bug);
+        let mut first_hit: Option<(usize, Contact)> = None;
+        for strand_index in strand_indices {
+            let strand = &self.web.strands[strand_index];
+            let start_sweep = Sweep::of(&self.web.particles[strand.start]);
+            let end_sweep = Sweep::of(&self.web.particles[strand.end]);
+            if let Some(contact) = sweep_sphere_against_segment(bug_sweep, start_sweep, end_sweep, bug_radius) {
+                if first_hit.is_none_or(|(_, first)| contact.time_of_impact < first.time_of_impact) {
+                    first_hit = Some((strand_index, contact));
+                }
+            }
+        }
+        first_hit
+    }
+
+    /// Checks the bugs' paths over the last (sub)step against the web, and
+    /// sticks every bug that hit it in the order they hit
     fn detect_collisions(&mut self) {
-        let bug_radius = 0.03;
+        self.web.update_strand_grid();
+        let collisions = map_range(self.parallel, self.bugs.len(), |i| {
+            self.find_collision(&self.bugs[i].particle, self.bugs[i].radius)
+        });
+
+        let mut hits: Vec<(usize, (usize, Contact))> = Vec::new();
+        for (bug_idx, hit) in collisions.into_iter().enumerate() {
+            let bug = &mut self.bugs[bug_idx];
+            match hit {
+                Some(_) if bug.escaping => {}
+                Some(hit) => hits.push((bug_idx, hit)),
+                None => bug.escaping = false,
+            }
+        }
+        hits.sort_by(|(a_idx, (_, a)), (b_idx, (_, b))| {
+            a.time_of_impact.total_cmp(&b.time_of_impact).then(a_idx.cmp(b_idx))
+        });
+
+        let mut caught = Vec::new();
+        // Strands that sticking bugs has added or moved to another index
+        let mut changed: Vec<usize> = Vec::new();
+        for (bug_idx, (strand_idx, contact)) in hits {
+            // A hit on a strand that's been split or moved no longer holds,
+            // but the bug may hit one of the new strands instead. Every
+            // other strand is just as it was when the hits were found.
+            let bug = &self.bugs[bug_idx];
+            let unchanged_hit = Some((strand_idx, contact)).filter(|_| !changed.contains(&strand_idx));
+            let changed_hit = self.first_hit_among(&bug.particle, bug.radius, changed.iter().copied());
+            let hit = match (unchanged_hit, changed_hit) {
+                (Some(a), Some(b)) if b.1.time_of_impact < a.1.time_of_impact => Some(b),
+                (Some(a), _) => Some(a),
+                (None, b) => b,
+            };
+            let Some((strand_idx, contact)) = hit else {
+                continue;
+            };
+            let time = self.sim_time - (1.0 - contact.time_of_impact) * self.last_timestep;
+            // The hit strand is swapped out for the last one, and the new
+            // strands go on the end
+            let strand_count = self.web.strands.len();
+            let impact_energy = self.stick_to_web(bug_idx, strand_idx, &contact, time);
+            for idx in std::iter::once(strand_idx).chain(strand_count - 1..self.web.strands.len()) {
+                if !changed.contains(&idx) {
+                    changed.push(idx);
+                }
+            }
+            self.events.push(SimEvent::BugCaught { time, contact, impact_energy });
+            caught.push(bug_idx);
+        }
+
+        // Remove from the highest index down so the rest stay where they are
+        caught.sort_unstable_by(|a, b| b.cmp(a));
+        for bug_idx in caught {
+            self.bugs.remove(bug_idx);
+        }
+    }
+
+    /// Removes the free bugs that have outlived their lifespan without being
+    /// caught
+    fn remove_expired_bugs(&mut self) {
         let mut bug_idx = 0;
-        let mut strand_idx = 0;
-        let mut has_stuck = false;
-
-        for (bug_index, bug) in self.bugs.iter().enumerate() {
-            for (strand_index, strand) in self.web.strands.iter().enumerate() {
-                let start_particle = &self.web.particles[strand.start];
-                let end_particle = &self.web.particles[strand.end];
-
-                let max_distance = strand.length + bug_radius;
-                let distance_to_start = (bug.position - start_particle.position).norm();
-                let distance_to_end = (bug.position - end_particle.position).norm();
-
-                // If the bug is too far from the silk strand then a collision cannot possible occur
-                // and we don't need to do the math
-                if distance_to_start > max_distance && distance_to_end > max_distance {
-                    continue;
+        while bug_idx < self.bugs.len() {
+            let particle = &self.bugs[bug_idx].particle;
+            if particle.time_alive < particle.lifespan {
+                bug_idx += 1;
+                continue;
+            }
+            self.events.push(SimEvent::BugExpired { time: self.sim_time, position: particle.position });
+            self.bugs.remove(bug_idx);
+        }
+    }
+
+    /// Changes which way caught bugs struggle when it's time to, and frees
+    /// the ones pulling on the web harder than it sticks to them
+    fn update_captured_bugs(&mut self) {
+        for captured in &mut self.captured {
+            if self.sim_time >= captured.next_struggle {
+                captured.struggle_direction = random_unit_vector(&mut self.rng);
+                captured.next_struggle = self.sim_time + self.struggle_period;
+            }
+        }
+
+        // Kept up to date as bugs are released, so the strands are only gone
+        // through once
+        let mut strands_on = self.web.strands_by_particle();
+        let mut captured_idx = 0;
+        while captured_idx < self.captured.len() {
+            let held_by = &strands_on[self.captured[captured_idx].particle_idx];
+            let max_tension = held_by.iter()
+                .map(|&i| {
+                    let strand = &self.web.strands[i];
+                    strand.tension((self.web.particles[strand.start].position - self.web.particles[strand.end].position).norm())
+                })
+                .fold(0.0, f64::max);
+            // A bug whose strands have all snapped is free too
+            if !held_by.is_empty() && max_tension <= self.captured[captured_idx].bug.adhesion {
+                captured_idx += 1;
+            } else {
+                self.release(captured_idx, &mut strands_on);
+            }
+        }
+    }
+
+    /// Pulls a caught bug out of the web and sets it flying again. If it was
+    /// stuck partway along a strand, the strand closes up behind it. Any other
+    /// silk it was stuck to stays where it is, joined at a point of silk left
+    /// in the bug's place, so the web isn't left with a hole in it.
+    /// `strands_on` lists the strands on each particle, and is kept in step
+    /// with the web.
+    fn release(&mut self, captured_idx: usize, strands_on: &mut Vec<Vec<usize>>) {
+        let captured = self.captured.swap_remove(captured_idx);
+        let particle_idx = captured.particle_idx;
+        let particle = self.web.particles[particle_idx];
+
+        // From the highest index down, since removing a strand moves the last one
+        let mut held_by = strands_on[particle_idx].clone();
+        held_by.sort_unstable_by(|a, b| b.cmp(a));
+        let other_end = |strand: &SilkStrand| if strand.start == particle_idx { strand.end } else { strand.start };
+
+        if held_by.len() == 2 || held_by.is_empty() {
+            let held_by: Vec<SilkStrand> = held_by.into_iter().map(|i| self.remove_listed_strand(i, strands_on)).collect();
+            if let [a, b] = held_by[..] {
+                let (start, end) = if a.end == particle_idx { (a, b) } else { (b, a) };
+                let length = start.length + end.length;
+                let joined = SilkStrand { start: other_end(&start), end: other_end(&end), length, ..start };
+                strands_on[joined.start].push(self.web.strands.len());
+                if joined.end != joined.start {
+                    strands_on[joined.end].push(self.web.strands.len());
                 }
+                self.web.push_strand(joined);
+            }
 
-                let strand_vector = end_particle.position - start_particle.position;
-                let bug_to_start = bug.position - start_particle.position;
-                let t = bug_to_start.dot(&strand_vector) / strand_vector.norm_squared();
-                let t_clamped = t.clamp(0.0, 1.0);
-                let closest_point = start_particle.position + strand_vector * t_clamped;
-
-                let distance = (closest_point - bug.position).norm();
-                // A collision occurred
-                if distance <= bug_radius {
-                    has_stuck = true;
-                    bug_idx = bug_index;
-                    strand_idx = strand_index;
-                    break;
+            let last_idx = self.web.particles.len() - 1;
+            self.web.remove_particle(particle_idx);
+            strands_on.swap_remove(particle_idx);
+            for other in &mut self.captured {
+                if other.particle_idx == last_idx {
+                    other.particle_idx = particle_idx;
                 }
             }
+        } else {
+            // The point left behind weighs as much as a strand's free end
+            let neighbour_mass: f64 = held_by.iter().map(|&i| self.web.particles[other_end(&self.web.strands[i])].mass).sum();
+            let mut silk = Particle::new(
+                particle.position,
+                particle.velocity,
+                neighbour_mass / (2.0 * held_by.len() as f64),
+                false,
+                ParticleType::Silk,
+            );
+            silk.prev_position = particle.prev_position;
+            self.web.particles[particle_idx] = silk;
         }
 
-        // Stick bugs to web for each detected collision
-        if has_stuck {
-            self.stick_to_web(bug_idx, strand_idx);
-            self.bugs.remove(bug_idx);
+        let mut bug = captured.bug;
+        bug.particle = particle;
+        // Its lifespan starts over, so it isn't lost the moment it's free
+        bug.particle.time_alive = 0.0;
+        bug.escaping = true;
+        self.bugs.push(bug);
+        self.events.push(SimEvent::BugEscaped { time: self.sim_time, time_to_escape: self.sim_time - captured.captured_at });
+    }
+
+    /// Removes a strand like `Spiderweb::remove_strand`, and takes it off
+    /// `strands_on` too, where the last strand takes its index
+    fn remove_listed_strand(&mut self, strand_idx: usize, strands_on: &mut [Vec<usize>]) -> SilkStrand {
+        let last_idx = self.web.strands.len() - 1;
+        let strand = self.web.remove_strand(strand_idx);
+        for particle in [strand.start, strand.end] {
+            strands_on[particle].retain(|&i| i != strand_idx);
+        }
+        if strand_idx != last_idx {
+            let moved = self.web.strands[strand_idx];
+            for particle in [moved.start, moved.end] {
+                for i in &mut strands_on[particle] {
+                    if *i == last_idx {
+                        *i = strand_idx;
+                    }
+                }
+            }
+        }
+        strand
+    }
+
+    fn bug_particle(&self, body: BugBody) -> Particle {
+        match body {
+            BugBody::Free(idx) => self.bugs[idx].particle,
+            BugBody::Captured(idx) => self.web.particles[self.captured[idx].particle_idx],
+        }
+    }
+
+    fn set_bug_particle(&mut self, body: BugBody, particle: Particle) {
+        match body {
+            BugBody::Free(idx) => self.bugs[idx].particle = particle,
+            BugBody::Captured(idx) => self.web.particles[self.captured[idx].particle_idx] = particle,
+        }
+    }
+
+    /// Pushes apart every pair of bugs, free or caught, that overlap. Pairs
+    /// are found with a grid over the bugs, like strands are, and resolved
+    /// one after another in a fixed order.
+    fn collide_bugs(&mut self) {
+        let bodies: Vec<(BugBody, f64)> = (0..self.bugs.len()).map(|i| (BugBody::Free(i), self.bugs[i].radius))
+            .chain((0..self.captured.len()).map(|i| (BugBody::Captured(i), self.captured[i].bug.radius)))
+            .collect();
+        if bodies.len() < 2 {
+            return;
+        }
+
+        let max_radius = bodies.iter().map(|&(_, radius)| radius).fold(0.0, f64::max);
+        let mut grid = SpatialGrid::new((2.0 * max_radius).max(1e-3));
+        let positions: Vec<Vector3<f64>> = bodies.iter().map(|&(body, _)| self.bug_particle(body).position).collect();
+        for (i, &(_, radius)) in bodies.iter().enumerate() {
+            grid.insert(i, positions[i].add_scalar(-radius), positions[i].add_scalar(radius));
+        }
+        let contacts = map_range(self.parallel, bodies.len(), |i| {
+            let radius = bodies[i].1;
+            grid.candidates(positions[i].add_scalar(-radius), positions[i].add_scalar(radius)).into_iter()
+                .filter(|&j| j > i && (positions[i] - positions[j]).norm() < radius + bodies[j].1)
+                .collect::<Vec<usize>>()
+        });
+
+        for (i, others) in contacts.into_iter().enumerate() {
+            for j in others {
+                self.resolve_bug_contact(bodies[i].0, bodies[j].0, bodies[i].1 + bodies[j].1);
+            }
+        }
+    }
+
+    /// Separates two overlapping bugs along the line between them, in
+    /// proportion to how light each is, and bounces them apart if they're
+    /// moving together
+    fn resolve_bug_contact(&mut self, a_body: BugBody, b_body: BugBody, min_distance: f64) {
+        let (mut a, mut b) = (self.bug_particle(a_body), self.bug_particle(b_body));
+        let offset = b.position - a.position;
+        let distance = offset.norm();
+        // Earlier contacts may have pushed them apart already
+        if distance >= min_distance {
+            return;
+        }
+        let inverse_mass = |p: &Particle| if p.fixed { 0.0 } else { 1.0 / p.mass };
+        let (a_weight, b_weight) = (inverse_mass(&a), inverse_mass(&b));
+        let total_weight = a_weight + b_weight;
+        if total_weight <= 0.0 {
+            return;
+        }
+        // Bugs in exactly the same place are pushed apart along an arbitrary axis
+        let normal = offset.try_normalize(1e-12).unwrap_or_else(Vector3::x);
+
+        let correction = normal * ((min_distance - distance) / total_weight);
+        let closing_speed = (b.velocity - a.velocity).dot(&normal);
+        let impulse = if closing_speed < 0.0 {
+            normal * (-(1.0 + self.bug_restitution) * closing_speed / total_weight)
+        } else {
+            Vector3::zeros()
+        };
+
+        // Move the verlet history along too, so position verlet sees the
+        // same change in velocity as everything else
+        for (particle, sign, weight) in [(&mut a, -1.0, a_weight), (&mut b, 1.0, b_weight)] {
+            let dv = impulse * (sign * weight);
+            particle.position += correction * (sign * weight);
+            particle.prev_position += correction * (sign * weight) - dv * self.last_timestep;
+            particle.velocity += dv;
+        }
+        self.set_bug_particle(a_body, a);
+        self.set_bug_particle(b_body, b);
+    }
+
+    /// Releases every bug the emitters have due by now
+    fn run_emitters(&mut self) {
+        let mut new_bugs = Vec::new();
+        for emitter in &mut self.emitters {
+            new_bugs.extend(emitter.emit(&mut self.rng, self.sim_time));
+        }
+        for bug in new_bugs {
+            self.spawn_bug(bug);
         }
     }
 
     pub fn step(&mut self) {
-        self.sim_time += self.timestep;
-        self.detect_collisions();
+        self.run_emitters();
+        self.wind.advance(self.sim_time + self.timestep);
+        if !self.adaptive.enabled {
+            self.substeps = 1;
+            self.remove_expired_bugs();
+            self.update_captured_bugs();
+            self.detect_collisions();
+            self.collide_bugs();
+            self.advance(self.timestep);
+            return;
+        }
 
-        let mut new_positions = vec![Vector3::zeros(); self.web.particles.len()];
-        let mut new_velocities = vec![Vector3::zeros(); self.web.particles.len()];
-        let mut new_bug_positions = vec![Vector3::zeros(); self.bugs.len()];
-        let mut new_bug_velocities = vec![Vector3::zeros(); self.bugs.len()];
-        let mut strands_to_remove = Vec::new();
+        let stable_timestep = self.adaptive.stable_timestep(&self.web, self.integrator);
+        let substeps = (self.timestep / stable_timestep).ceil().clamp(1.0, self.adaptive.max_substeps as f64);
+        let mut substep = self.timestep / substeps;
+        let mut remaining = self.timestep;
+        self.substeps = 0;
+
+        while remaining > 0.0 {
+            // Finish the step when less than a substep is left, so rounding
+            // never leaves a sliver of a substep behind
+            let timestep = if remaining <= substep * (1.0 + 1e-9) { remaining } else { substep };
+            let saved = self.snapshot();
+            // Bugs are caught between substeps so none can pass through a
+            // strand that moved during the step
+            self.remove_expired_bugs();
+            self.update_captured_bugs();
+            self.detect_collisions();
+            self.collide_bugs();
+            let energy_before = self.web.energy();
+            self.advance(timestep);
 
-        for (i, particle) in self.web.particles.iter().enumerate() {
-            let (new_position, new_velocity) = self.update_particle(particle, &mut strands_to_remove);
-            new_positions[i] = new_position;
-            new_velocities[i] = new_velocity;
+            let Some(divergence) = self.adaptive.check(&self.web, energy_before) else {
+                remaining -= timestep;
+                self.substeps += 1;
+                continue;
+            };
+
+            if timestep / 2.0 < self.adaptive.min_timestep {
+                if divergence == Divergence::NonFinite {
+                    self.restore(saved);
+                    self.events.push(SimEvent::Unresolved { time: self.sim_time, divergence });
+                    return;
+                }
+                self.events.push(SimEvent::Unresolved { time: self.sim_time, divergence });
+                remaining -= timestep;
+                self.substeps += 1;
+                continue;
+            }
+
+            self.restore(saved);
+            substep = timestep / 2.0;
+            self.events.push(SimEvent::RolledBack { time: self.sim_time, divergence, retry_timestep: substep });
         }
+    }
 
-        for (i, bug) in self.bugs.iter().enumerate() {
-            new_bug_positions[i] = bug.position + bug.velocity * self.timestep;
-            new_bug_velocities[i] = bug.velocity;
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            web: self.web.clone(),
+            bugs: self.bugs.clone(),
+            captured: self.captured.clone(),
+            rng: self.rng.clone(),
+            sim_time: self.sim_time,
+            last_timestep: self.last_timestep,
+            event_count: self.events.len(),
         }
+    }
+
+    /// Puts everything back as it was when the snapshot was taken. Anything
+    /// that happened since never happened, so its events are dropped too.
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.web = snapshot.web;
+        self.bugs = snapshot.bugs;
+        self.captured = snapshot.captured;
+        self.rng = snapshot.rng;
+        self.sim_time = snapshot.sim_time;
+        self.last_timestep = snapshot.last_timestep;
+        self.events.truncate(snapshot.event_count);
+    }
+
+    /// Moves the web and the free bugs forward by `timestep`
+    fn advance(&mut self, timestep: f64) {
+        self.sim_time += timestep;
+
+        // Position verlet takes the previous position to be one timestep back,
+        // so stretch or squash the history when the timestep changes
+        if timestep != self.last_timestep {
+            let scale = timestep / self.last_timestep;
+            for particle in self.web.particles.iter_mut().chain(self.bugs.iter_mut().map(|bug| &mut bug.particle)) {
+                particle.prev_position = particle.position - (particle.position - particle.prev_position) * scale;
+            }
+            self.last_timestep = timestep;
+        }
+
+        let mut strands_to_remove = Vec::new();
+        let positions: Vec<Vector3<f64>> = self.web.particles.iter().map(|p| p.position).collect();
+        let velocities: Vec<Vector3<f64>> = self.web.particles.iter().map(|p| p.velocity).collect();
+        let (new_positions, new_velocities) = match self.integrator {
+            Integrator::BackwardEuler => {
+                let forces = self.calculate_forces(&positions, &velocities, Some(&mut strands_to_remove));
+                self.implicit_solver.step(&self.web, &forces, timestep)
+            }
+            Integrator::Xpbd => {
+                let forces = self.calculate_external_forces(&positions, &velocities);
+                let (new_positions, new_velocities, strand_forces) = self.xpbd_solver.step(&self.web, &forces, timestep);
+                for (i, &force) in strand_forces.iter().enumerate() {
+                    if self.strand_breaks(i, force, &positions) {
+                        strands_to_remove.push(i);
+                    }
+                }
+                (new_positions, new_velocities)
+            }
+            integrator => {
+                // Only the forces at the start of the step decide which strands snap
+                let mut overloaded = Some(&mut strands_to_remove);
+                integrator.integrate(&self.web.particles, timestep, self.parallel, |positions, velocities| {
+                    self.calculate_forces(positions, velocities, overloaded.take())
+                })
+            }
+        };
+
+        self.steer_bugs(timestep);
+        let bug_particles: Vec<Particle> = self.bugs.iter().map(|bug| bug.particle).collect();
+        let (new_bug_positions, new_bug_velocities) = self.integrator.integrate(&bug_particles, timestep, self.parallel, |positions, velocities| {
+            self.calculate_bug_forces(&bug_particles, positions, velocities)
+        });
 
         for (i, particle) in self.web.particles.iter_mut().enumerate() {
             if particle.fixed {
@@ -195,27 +783,803 @@ impl Simulator {
             particle.velocity = new_velocities[i];
         }
 
+        for particle in self.web.particles.iter_mut().chain(self.bugs.iter_mut().map(|bug| &mut bug.particle)) {
+            particle.time_alive += timestep;
+        }
+
         for (i, bug) in self.bugs.iter_mut().enumerate() {
-            bug.prev_position = bug.position;
-            bug.position = new_bug_positions[i];
-            bug.velocity = new_bug_velocities[i];
+            bug.particle.prev_position = bug.particle.position;
+            bug.particle.position = new_bug_positions[i];
+            bug.particle.velocity = new_bug_velocities[i];
         }
 
-        // TODO: Properly handle strand breakages
-        // for (strand_index, conn_particle) in strands_to_remove {
-        //     let new_particle = self.web.particles[conn_particle].clone();
-        //     self.web.push_particle(new_particle);
-        //     let mut new_strand = self.web.strands[strand_index].clone();
-        //     if new_strand.start == conn_particle {
-        //         new_strand.start = self.web.particles.len() - 1;
-        //     } else {
-        //         new_strand.end = self.web.particles.len() - 1;
-        //     }
-        //     self.web.strands[strand_index] = new_strand;
-        // }
+        // Break from the highest index down since swap_remove only moves strands
+        // that come after the one being removed.
+        strands_to_remove.sort_unstable_by(|a, b| b.cmp(a));
+        for strand_index in strands_to_remove {
+            self.web.break_strand(strand_index);
+        }
     }
 
     pub fn get_web(&mut self) -> &mut Spiderweb {
         &mut self.web
     }
-}
\ No newline at end of file
+
+    pub fn web(&self) -> &Spiderweb {
+        &self.web
+    }
+
+    /// Takes every event that happened since the last call
+    pub fn drain_events(&mut self) -> Vec<SimEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forces::{QuadraticDrag, Springs, StrandDrag};
+    use crate::wind::ConstantWind;
+    use crate::bug::{Behaviour, BugSpecies};
+    use crate::emitter::{EmitterKind, ScheduledSpawn};
+
+    /// Two free particles in the same place, each held by its own strand to an
+    /// anchor on opposite sides, as happens right after a strand snaps.
+    fn coincident_web() -> Spiderweb {
+        let mut web = Spiderweb::new();
+        web.push_particle(Particle::new(Vector3::new(-1.0, 0.0, 0.0), Vector3::zeros(), 1.0, true, ParticleType::Silk));
+        web.push_particle(Particle::new(Vector3::new(1.0, 0.0, 0.0), Vector3::zeros(), 1.0, true, ParticleType::Silk));
+        web.push_particle(Particle::new(Vector3::zeros(), Vector3::zeros(), 1.0, false, ParticleType::Silk));
+        web.push_particle(Particle::new(Vector3::zeros(), Vector3::zeros(), 1.0, false, ParticleType::Silk));
+        web.push_strand(SilkStrand::new(0, 2, 0.5, 100.0, 0.0));
+        web.push_strand(SilkStrand::new(3, 1, 0.5, 100.0, 0.0));
+        web
+    }
+
+    /// A sagging line of particles between two fixed anchors
+    fn chain_web(stiffness: f64) -> Spiderweb {
+        let mut web = Spiderweb::new();
+        let count = 10;
+        for i in 0..=count {
+            let position = Vector3::new(i as f64 / count as f64, 0.0, 0.0);
+            let fixed = i == 0 || i == count;
+            web.push_particle(Particle::new(position, Vector3::zeros(), 1.0, fixed, ParticleType::Silk));
+        }
+        for i in 0..count {
+            web.push_strand(SilkStrand::new(i, i + 1, 0.1, stiffness, 10.0));
+        }
+        web
+    }
+
+    #[test]
+    fn backward_euler_is_stable_for_stiff_silk() {
+        let mut sim = Simulator::new(0.01, chain_web(1e6));
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.gravity = Vector3::new(0.0, -10.0, 0.0);
+        sim.integrator = Integrator::BackwardEuler;
+        for _ in 0..500 {
+            sim.step();
+        }
+        for particle in &sim.web.particles {
+            assert!(particle.position.iter().all(|x| x.is_finite()));
+            assert!(particle.position.y.abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn xpbd_settles_where_forces_balance() {
+        let settled = |integrator: Integrator| {
+            let mut sim = Simulator::new(0.01, chain_web(1000.0));
+            sim.max_silk_strand_force = f64::INFINITY;
+            sim.gravity = Vector3::new(0.0, -10.0, 0.0);
+            sim.wind = Box::new(ConstantWind::calm());
+            sim.integrator = integrator;
+            for _ in 0..2000 {
+                sim.step();
+            }
+            sim.web
+        };
+        let explicit = settled(Integrator::PositionVerlet);
+        let xpbd = settled(Integrator::Xpbd);
+        for (a, b) in explicit.particles.iter().zip(&xpbd.particles) {
+            assert!((a.position - b.position).norm() < 1e-3, "{} vs {}", a.position, b.position);
+            assert!(b.velocity.norm() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn xpbd_follows_the_silk_curve() {
+        // A weight hanging from dragline silk, heavy enough to stretch it
+        // well past where it yields
+        let silk = SilkMaterial::MAJOR_AMPULLATE;
+        let weight = 1000.0 * 0.1 * silk.stress(0.1);
+        let mut web = Spiderweb::new();
+        web.push_particle(Particle::new(Vector3::zeros(), Vector3::zeros(), 1.0, true, ParticleType::Silk));
+        web.push_particle(Particle::new(Vector3::new(0.0, -0.1, 0.0), Vector3::zeros(), 1.0, false, ParticleType::Silk));
+        web.push_strand(SilkStrand { material: silk, ..SilkStrand::new(0, 1, 0.1, 1000.0, 10.0) });
+        let mut sim = Simulator::new(0.01, web);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.gravity = Vector3::new(0.0, -weight, 0.0);
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.integrator = Integrator::Xpbd;
+        for _ in 0..2000 {
+            sim.step();
+        }
+
+        let len = sim.web.particles[1].position.norm();
+        assert!((sim.web.strands[0].strain(len) - 0.1).abs() < 1e-3);
+        assert!((sim.web.strands[0].tension(len) - weight).abs() < 1e-3 * weight);
+        let (_, _, strand_forces) = sim.xpbd_solver.step(&sim.web, &[Vector3::zeros(), sim.gravity], sim.timestep);
+        assert!((strand_forces[0] - weight).abs() < 1e-3 * weight);
+    }
+
+    #[test]
+    fn adaptive_substeps_keep_stiff_silk_stable() {
+        let mut sim = Simulator::new(0.01, chain_web(1e6));
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.gravity = Vector3::new(0.0, -10.0, 0.0);
+        sim.adaptive.enabled = true;
+        for _ in 0..100 {
+            sim.step();
+        }
+        assert!(sim.substeps > 1);
+        assert!(sim.drain_events().is_empty());
+        for particle in &sim.web.particles {
+            assert!(particle.position.y.abs() < 0.1);
+        }
+    }
+
+    #[test]
+    fn parallel_step_matches_single_threaded() {
+        let web = crate::webgen::Webgen::new().realistic_web();
+        let mut single = Simulator::new(0.01, web.clone());
+        let mut parallel = Simulator::new(0.01, web);
+        single.parallel = false;
+        parallel.parallel = true;
+        for sim in [&mut single, &mut parallel] {
+            sim.seed(1);
+            for i in 0..20 {
+                let offset = i as f64 * 0.01;
+                sim.add_bug(Vector3::new(offset, 0.1, 1.0), Vector3::new(0.0, 0.0, -2.0), 2.0, f64::INFINITY);
+            }
+        }
+
+        for _ in 0..200 {
+            single.step();
+            parallel.step();
+        }
+        assert_eq!(single.web.particles, parallel.web.particles);
+        assert_eq!(single.bugs, parallel.bugs);
+    }
+
+    #[test]
+    fn coincident_particles_get_separate_forces() {
+        let mut sim = Simulator::new(0.01, coincident_web());
+        sim.max_silk_strand_force = f64::INFINITY;
+        assert_eq!(sim.web.particles[2], sim.web.particles[3]);
+
+        let positions: Vec<Vector3<f64>> = sim.web.particles.iter().map(|p| p.position).collect();
+        let velocities: Vec<Vector3<f64>> = sim.web.particles.iter().map(|p| p.velocity).collect();
+        let spring_forces = Springs.forces(&sim, &sim.web_bodies(&positions, &velocities));
+        assert_eq!(spring_forces[2], Vector3::new(-50.0, 0.0, 0.0));
+        assert_eq!(spring_forces[3], Vector3::new(50.0, 0.0, 0.0));
+
+        sim.gravity = Vector3::zeros();
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.step();
+        assert!(sim.web.particles[2].position.x < 0.0);
+        assert!(sim.web.particles[3].position.x > 0.0);
+    }
+
+    #[test]
+    fn grid_lookups_match_checking_every_strand() {
+        let mut sim = Simulator::new(0.01, crate::webgen::Webgen::new().realistic_web());
+        sim.add_bug(Vector3::new(0.05, 0.1, 1.0), Vector3::new(0.0, 0.0, -2.0), 2.0, f64::INFINITY);
+        for _ in 0..100 {
+            sim.step();
+        }
+        // Every point along every strand finds it, however strands were removed
+        let assert_every_strand_filed = |web: &Spiderweb| {
+            for (strand_idx, strand) in web.strands.iter().enumerate() {
+                let (start, end) = (web.particles[strand.start].position, web.particles[strand.end].position);
+                for t in 0..=20 {
+                    let point = start.lerp(&end, t as f64 / 20.0);
+                    assert!(web.strands_in_box(point, point).contains(&strand_idx), "lost strand {strand_idx} at {point}");
+                }
+            }
+        };
+        sim.web.update_strand_grid();
+        assert_every_strand_filed(&sim.web);
+        sim.web.break_strand(3);
+        assert_every_strand_filed(&sim.web);
+        for i in 0..20 {
+            sim.web.remove_strand(i * 7 % sim.web.strands.len());
+            assert_every_strand_filed(&sim.web);
+        }
+        // Moved further than the grid's margin, so its strands have to be filed again
+        sim.web.particles[10].position += Vector3::new(0.3, 0.2, 0.0);
+        sim.web.update_strand_grid();
+        assert_every_strand_filed(&sim.web);
+
+        let web = &sim.web;
+        let brute_force_closest = |pos: Vector3<f64>| (0..web.strands.len())
+            .min_by(|&a, &b| web.distance_to_strand(pos, a).total_cmp(&web.distance_to_strand(pos, b)))
+            .unwrap();
+        for i in 0..200 {
+            let angle = i as f64 * 0.7;
+            let radius = i as f64 * 0.01;
+            let pos = Vector3::new(radius * angle.cos(), radius * angle.sin(), 0.1 * angle.sin());
+            assert_eq!(web.get_closest_strand(pos), brute_force_closest(pos));
+
+            let near = web.strands_in_box(pos.add_scalar(-0.03), pos.add_scalar(0.03));
+            for strand_idx in 0..web.strands.len() {
+                if web.distance_to_strand(pos, strand_idx) <= 0.03 {
+                    assert!(near.contains(&strand_idx), "missed strand {strand_idx} near {pos}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fast_bug_is_caught_between_steps() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        // Crosses the chain's plane within one step, far from the chain at
+        // both ends of the step
+        sim.add_bug(Vector3::new(0.55, 0.0, 2.0), Vector3::new(0.0, 0.0, -400.0), 2.0, f64::INFINITY);
+        sim.step();
+        sim.step();
+
+        assert!(sim.bugs.is_empty());
+        let caught: Vec<SimEvent> = sim.drain_events();
+        let [SimEvent::BugCaught { time, contact, .. }] = caught[..] else {
+            panic!("expected one catch, got {caught:?}");
+        };
+        assert!(contact.time_of_impact > 0.0 && contact.time_of_impact < 1.0);
+        assert!((contact.strand_param - 0.5).abs() < 0.1);
+        assert!(time > 0.0 && time < sim.sim_time);
+    }
+
+    #[test]
+    fn every_bug_touching_the_web_is_caught() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        sim.max_silk_strand_force = f64::INFINITY;
+        // Two bugs hit the same strand and a third hits another one
+        for x in [0.52, 0.57, 0.25] {
+            sim.add_bug(Vector3::new(x, 0.0, 0.02), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+        }
+        sim.add_bug(Vector3::new(0.5, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+        let particles = sim.web.particles.len();
+        let strands = sim.web.strands.len();
+        sim.step();
+
+        assert_eq!(sim.bugs.len(), 1);
+        assert!(sim.bugs[0].particle.position.z < 1.0);
+        assert_eq!(sim.web.particles.len(), particles + 3);
+        assert_eq!(sim.web.strands.len(), strands + 3);
+        // Every particle is still on the chain, with no strand left dangling
+        let mut connections = vec![0; sim.web.particles.len()];
+        for strand in &sim.web.strands {
+            connections[strand.start] += 1;
+            connections[strand.end] += 1;
+        }
+        assert!(connections.iter().enumerate().all(|(i, &n)| n == if sim.web.particles[i].fixed { 1 } else { 2 }));
+    }
+
+    #[test]
+    fn caught_bug_carries_its_momentum_into_the_web() {
+        let mut sim = Simulator::new(0.001, chain_web(1000.0));
+        sim.seed(1);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.gravity = Vector3::zeros();
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.add_bug(Vector3::new(0.55, 0.0, 0.03), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+        sim.step();
+        let caught = sim.drain_events();
+        assert!(matches!(caught[..], [SimEvent::BugCaught { impact_energy, .. }] if impact_energy == 1.0));
+
+        let bug_idx = sim.web.particles.len() - 1;
+        for _ in 0..100 {
+            sim.step();
+        }
+        // The bug is slowed by the web rather than stopped dead, and drags
+        // its neighbours along with it. It's put on the strand where it hit,
+        // so the strand holds it back from the moment it's caught and it's
+        // lost well over half its speed by now.
+        let bug = sim.web.particles[bug_idx];
+        assert!(bug.velocity.z < -0.25 && bug.velocity.z > -0.5);
+        assert!(sim.web.particles[5].position.z < 0.0 && sim.web.particles[6].position.z < 0.0);
+    }
+
+    #[test]
+    fn bugs_attach_where_they_hit() {
+        for attachment in [Attachment::Embedded, Attachment::Hanging] {
+            let mut sim = Simulator::new(0.01, chain_web(1000.0));
+            sim.attachment = attachment;
+            // Hits the strand from 0.5 to 0.6 a quarter of the way along
+            sim.add_bug(Vector3::new(0.525, 0.0, 0.02), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+            sim.step();
+
+            let strands = &sim.web.strands;
+            let bug_idx = sim.web.particles.iter().position(|p| p.particle_type == ParticleType::Bug).unwrap();
+            let contact_idx = match attachment {
+                Attachment::Embedded => bug_idx,
+                Attachment::Hanging => {
+                    let thread = strands.iter().find(|s| s.end == bug_idx).unwrap();
+                    assert!((thread.length - 0.02).abs() < 1e-9);
+                    thread.start
+                }
+            };
+            let halves: Vec<f64> = strands.iter()
+                .filter(|s| s.end == contact_idx && s.start != bug_idx || s.start == contact_idx && s.end != bug_idx)
+                .map(|s| s.length)
+                .collect();
+            assert_eq!(halves.len(), 2);
+            assert!((halves[0] - 0.025).abs() < 1e-9 && (halves[1] - 0.075).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn free_bugs_feel_forces_and_thrust() {
+        let mut sim = Simulator::new(0.01, Spiderweb::new());
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.add_bug(Vector3::new(0.0, 0.0, 1.0), Vector3::zeros(), 2.0, f64::INFINITY);
+        for _ in 0..100 {
+            sim.step();
+        }
+        // Falls under gravity, slower than it would without drag
+        let fallen = sim.bugs[0].particle.position.y;
+        assert!(fallen < 0.0 && fallen > 0.5 * sim.gravity.y);
+
+        sim.bug_thrust = 1.0;
+        sim.bugs[0].behaviour = Behaviour::SeekLight { light: Vector3::new(0.0, fallen, -1.0) };
+        for _ in 0..100 {
+            sim.step();
+        }
+        assert!(sim.bugs[0].particle.position.z < 0.9);
+    }
+
+    #[test]
+    fn bugs_steer_clear_of_visible_strands() {
+        let caught_with_visibility = |strand_visibility: f64| {
+            let mut sim = Simulator::new(0.01, chain_web(1000.0));
+            sim.wind = Box::new(ConstantWind::calm());
+            sim.bug_thrust = 0.5;
+            sim.senses.strand_visibility = strand_visibility;
+            let mut bug = Bug::new(Vector3::new(0.55, 0.1, 0.5), Vector3::zeros(), 2.0);
+            bug.behaviour = Behaviour::SeekLight { light: Vector3::new(0.55, 0.0, -1.0) };
+            sim.spawn_bug(bug);
+            for _ in 0..300 {
+                sim.step();
+            }
+            sim.bugs.is_empty()
+        };
+        assert!(caught_with_visibility(0.0));
+        assert!(!caught_with_visibility(1.0));
+    }
+
+    #[test]
+    fn struggling_bugs_can_pull_free() {
+        let escapes_with_struggle = |struggle_force: f64| {
+            let mut sim = Simulator::new(0.001, chain_web(1000.0));
+            sim.seed(1);
+            sim.max_silk_strand_force = f64::INFINITY;
+            sim.wind = Box::new(ConstantWind::calm());
+            let mut bug = Bug::new(Vector3::new(0.55, 0.0, 0.03), Vector3::new(0.0, 0.0, -0.1), 0.1);
+            bug.struggle_force = struggle_force;
+            sim.spawn_bug(bug);
+            let strands = sim.web.strands.len();
+            let particles = sim.web.particles.len();
+
+            for _ in 0..2000 {
+                sim.step();
+                let escape = sim.drain_events().into_iter().find_map(|event| match event {
+                    SimEvent::BugEscaped { time_to_escape, .. } => Some(time_to_escape),
+                    _ => None,
+                });
+                if let Some(time_to_escape) = escape {
+                    assert!(time_to_escape > 0.0);
+                    // The strand the bug was stuck to closes up behind it
+                    assert_eq!(sim.web.strands.len(), strands);
+                    assert_eq!(sim.web.particles.len(), particles);
+                    assert!(sim.captured.is_empty() && sim.bugs.len() == 1);
+                    return true;
+                }
+            }
+            assert_eq!(sim.captured.len(), 1);
+            false
+        };
+        assert!(!escapes_with_struggle(1.0));
+        assert!(escapes_with_struggle(50.0));
+    }
+
+    #[test]
+    fn bugs_let_go_together_leave_the_web_whole() {
+        let mut sim = Simulator::new(0.001, chain_web(1000.0));
+        sim.seed(1);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.wind = Box::new(ConstantWind::calm());
+        let (strands, particles) = (sim.web.strands.len(), sim.web.particles.len());
+        // Two of them stuck to the same strand, so they share a piece of it
+        for x in [0.25, 0.515, 0.585] {
+            sim.spawn_bug(Bug::new(Vector3::new(x, 0.0, 0.03), Vector3::new(0.0, 0.0, -0.1), 0.1));
+        }
+        sim.step();
+        assert_eq!(sim.captured.len(), 3);
+
+        for captured in &mut sim.captured {
+            captured.bug.adhesion = -1.0;
+        }
+        sim.update_captured_bugs();
+        assert!(sim.captured.is_empty() && sim.bugs.len() == 3);
+        assert_eq!((sim.web.strands.len(), sim.web.particles.len()), (strands, particles));
+        let rest_length: f64 = sim.web.strands.iter().map(|s| s.length).sum();
+        assert!((rest_length - 1.0).abs() < 1e-9);
+        assert!(sim.web.strands_by_particle().iter().all(|on| !on.is_empty()));
+    }
+
+    #[test]
+    fn escaping_bugs_leave_junctions_in_the_web() {
+        let mut sim = Simulator::new(0.001, chain_web(1000.0));
+        sim.seed(1);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.wind = Box::new(ConstantWind::calm());
+        let mut bug = Bug::new(Vector3::new(0.55, 0.0, 0.03), Vector3::new(0.0, 0.0, -0.1), 0.1);
+        bug.struggle_force = 50.0;
+        sim.spawn_bug(bug);
+        sim.step();
+        // A third strand holds the bug up to the first anchor
+        let bug_idx = sim.captured[0].particle_idx;
+        sim.web.push_strand(SilkStrand::new(0, bug_idx, 0.55, 1000.0, 10.0));
+        let strands = sim.web.strands.len();
+        let particles = sim.web.particles.len();
+
+        while sim.bugs.is_empty() {
+            sim.step();
+        }
+        assert!(sim.captured.is_empty());
+        assert_eq!(sim.web.strands.len(), strands);
+        assert_eq!(sim.web.particles.len(), particles);
+        let junction = sim.web.particles[bug_idx];
+        assert_eq!(junction.particle_type, ParticleType::Silk);
+        assert_eq!(junction.mass, 0.5);
+        assert_eq!(sim.web.strands.iter().filter(|s| s.start == bug_idx || s.end == bug_idx).count(), 3);
+    }
+
+    #[test]
+    fn rolled_back_substeps_leave_no_trace() {
+        let mut sim = Simulator::new(0.01, chain_web(1e6));
+        sim.seed(1);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.gravity = Vector3::new(0.0, -10.0, 0.0);
+        sim.adaptive.enabled = true;
+        // Substeps too long to be stable, so they keep being rolled back
+        sim.adaptive.stability_factor = 8.0;
+        let mut bug = Bug::new(Vector3::new(0.55, 0.0, 0.05), Vector3::new(0.0, 0.0, -2.0), 2.0);
+        bug.adhesion = f64::INFINITY;
+        sim.spawn_bug(bug);
+        let mut events = Vec::new();
+        for _ in 0..10 {
+            sim.step();
+            events.extend(sim.drain_events());
+        }
+        assert!(events.iter().any(|event| matches!(event, SimEvent::RolledBack { .. })));
+        assert_eq!(events.iter().filter(|event| matches!(event, SimEvent::BugCaught { .. })).count(), 1);
+        assert_eq!(sim.captured.len(), 1);
+    }
+
+    #[test]
+    fn bugs_that_miss_the_web_expire() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        sim.add_bug(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, 1.0), 2.0, 0.5);
+        sim.add_bug(Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, 1.0), 2.0, f64::INFINITY);
+        for _ in 0..49 {
+            sim.step();
+        }
+        assert_eq!(sim.bugs.len(), 2);
+        sim.step();
+        sim.step();
+
+        assert_eq!(sim.bugs.len(), 1);
+        assert_eq!(sim.bugs[0].particle.lifespan, f64::INFINITY);
+        assert!((sim.bugs[0].particle.time_alive - 0.51).abs() < 1e-9);
+        assert!(matches!(sim.drain_events()[..], [SimEvent::BugExpired { .. }]));
+    }
+
+    #[test]
+    fn escaped_bugs_live_out_a_new_lifespan() {
+        let mut sim = Simulator::new(0.001, chain_web(1000.0));
+        sim.seed(1);
+        sim.max_silk_strand_force = f64::INFINITY;
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.add_bug(Vector3::new(0.55, 0.0, 0.03), Vector3::new(0.0, 0.0, -0.1), 0.1, 0.05);
+        // Held for longer than it would have lived
+        for _ in 0..100 {
+            sim.step();
+        }
+        assert_eq!(sim.captured.len(), 1);
+
+        sim.captured[0].bug.adhesion = -1.0;
+        for _ in 0..40 {
+            sim.step();
+        }
+        assert_eq!(sim.bugs.len(), 1);
+        assert!(!sim.drain_events().iter().any(|event| matches!(event, SimEvent::BugExpired { .. })));
+        for _ in 0..20 {
+            sim.step();
+        }
+        assert!(sim.bugs.is_empty());
+        assert!(matches!(sim.drain_events()[..], [SimEvent::BugExpired { .. }]));
+    }
+
+    #[test]
+    fn bugs_collide_at_their_own_size() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        sim.seed(1);
+        sim.gravity = Vector3::zeros();
+        sim.wind = Box::new(ConstantWind::calm());
+        // Both pass 2cm from the chain
+        for species in [BugSpecies::FRUIT_FLY, BugSpecies::MOTH] {
+            let mut bug = species.spawn(&mut sim.rng, Vector3::new(0.55, 0.02, 0.5), Vector3::new(0.0, 0.0, -1.0));
+            bug.adhesion = f64::INFINITY;
+            sim.spawn_bug(bug);
+        }
+        for _ in 0..100 {
+            sim.step();
+        }
+        assert_eq!(sim.bugs.len(), 1);
+        assert!(sim.bugs[0].radius < 0.02);
+        assert!(sim.captured[0].bug.radius > 0.02);
+    }
+
+    #[test]
+    fn emitters_release_bugs_on_schedule() {
+        let mut sim = Simulator::new(0.01, Spiderweb::new());
+        sim.seed(1);
+        let away = Vector3::new(0.0, 0.0, 1.0);
+        sim.emitters.push(Emitter::new(
+            EmitterKind::Point { position: Vector3::zeros(), direction: away, spread: 0.1, rate: 10.0 },
+            BugSpecies::MOTH,
+        ));
+        sim.emitters.push(Emitter::new(
+            EmitterKind::Swarm { centre: Vector3::zeros(), radius: 0.1, target: away, rate: 100.0 },
+            BugSpecies::FRUIT_FLY,
+        ));
+        let script = [0.25, 0.5].map(|time| ScheduledSpawn { time, position: Vector3::zeros(), direction: away });
+        sim.emitters.push(Emitter::new(EmitterKind::Scripted { spawns: script.to_vec() }, BugSpecies::BEE));
+
+        // Stop just short of a second so floating point error can't add or
+        // drop the bug due at the very end
+        for _ in 0..99 {
+            sim.step();
+        }
+        let count = |species: BugSpecies| sim.bugs.iter().filter(|bug| bug.adhesion == species.adhesion && bug.struggle_force == species.struggle_force).count();
+        assert_eq!(count(BugSpecies::MOTH), 10);
+        assert_eq!(count(BugSpecies::BEE), 2);
+        // Poisson arrivals, so only about as many as the rate
+        assert!((70..130).contains(&count(BugSpecies::FRUIT_FLY)));
+    }
+
+    #[test]
+    fn emitters_added_later_start_from_then() {
+        let mut sim = Simulator::new(0.01, Spiderweb::new());
+        sim.seed(1);
+        for _ in 0..100 {
+            sim.step();
+        }
+        let away = Vector3::new(0.0, 0.0, 1.0);
+        sim.emitters.push(Emitter::new(
+            EmitterKind::Point { position: Vector3::zeros(), direction: away, spread: 0.1, rate: 10.0 },
+            BugSpecies::MOTH,
+        ));
+        let script = [0.5, sim.sim_time + 0.25].map(|time| ScheduledSpawn { time, position: Vector3::zeros(), direction: away });
+        sim.emitters.push(Emitter::new(EmitterKind::Scripted { spawns: script.to_vec() }, BugSpecies::BEE));
+
+        // One bug straight away rather than a burst of all those it would
+        // have released over the last second, and none of the script that's
+        // already passed
+        sim.step();
+        assert_eq!(sim.bugs.len(), 1);
+        for _ in 0..30 {
+            sim.step();
+        }
+        let count = |species: BugSpecies| sim.bugs.iter().filter(|bug| bug.adhesion == species.adhesion && bug.struggle_force == species.struggle_force).count();
+        assert!((3..=4).contains(&count(BugSpecies::MOTH)));
+        assert_eq!(count(BugSpecies::BEE), 1);
+    }
+
+    #[test]
+    fn bugs_bounce_off_each_other() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        sim.gravity = Vector3::zeros();
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.drag_coefficient = 0.0;
+        // One bug caught in the chain, then two more flying head on into each
+        // other, the second of them right onto the caught one
+        sim.add_bug(Vector3::new(0.55, 0.0, 0.03), Vector3::zeros(), 2.0, f64::INFINITY);
+        sim.step();
+        assert_eq!(sim.captured.len(), 1);
+        sim.add_bug(Vector3::new(0.0, 1.0, 0.5), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+        sim.add_bug(Vector3::new(0.0, 1.0, -0.5), Vector3::new(0.0, 0.0, 1.0), 2.0, f64::INFINITY);
+        sim.add_bug(Vector3::new(0.55, 0.0, 0.2), Vector3::new(0.0, 0.0, -1.0), 2.0, f64::INFINITY);
+        for _ in 0..60 {
+            sim.step();
+        }
+
+        let (a, b) = (sim.bugs[0].particle, sim.bugs[1].particle);
+        assert!(a.velocity.z > 0.0 && b.velocity.z < 0.0);
+        let positions: Vec<_> = sim.bugs.iter().map(|bug| bug.particle.position)
+            .chain(sim.captured.iter().map(|captured| sim.web.particles[captured.particle_idx].position))
+            .collect();
+        assert_eq!(positions.len(), 4);
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                assert!((positions[i] - positions[j]).norm() > 0.055, "bugs {} and {} overlap", i, j);
+            }
+        }
+    }
+
+    struct Updraft;
+
+    impl ForceGenerator for Updraft {
+        fn name(&self) -> &str {
+            "Updraft"
+        }
+
+        fn forces(&self, _sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+            vec![Vector3::new(0.0, 1.0, 0.0); bodies.positions.len()]
+        }
+    }
+
+    #[test]
+    fn custom_forces_are_applied_and_reported() {
+        let mut sim = Simulator::new(0.01, Spiderweb::new());
+        sim.gravity = Vector3::new(0.0, -1.0, 0.0);
+        sim.wind = Box::new(ConstantWind::calm());
+        sim.force_generators.push(Box::new(Updraft));
+        sim.add_bug(Vector3::zeros(), Vector3::new(2.0, 0.0, 0.0), 1.0, f64::INFINITY);
+
+        let contributions = sim.force_contributions();
+        let names: Vec<&str> = contributions.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Gravity", "Linear Drag", "Wind", "Strand Drag", "Springs", "Updraft"]);
+        // Drag is only counted once
+        assert_eq!(contributions[1].net_force, Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!(contributions[1].power, -2.0);
+
+        // The updraft holds the bug up against gravity
+        sim.step();
+        assert!(sim.bugs[0].particle.velocity.y.abs() < 1e-12);
+    }
+
+    /// Pulls every strand tight without moving anything
+    struct Tension;
+
+    impl ForceGenerator for Tension {
+        fn name(&self) -> &str {
+            "Tension"
+        }
+
+        fn forces(&self, sim: &Simulator, bodies: &Bodies) -> Vec<Vector3<f64>> {
+            self.generate(sim, bodies).particles
+        }
+
+        fn generate(&self, _sim: &Simulator, bodies: &Bodies) -> GeneratedForces {
+            GeneratedForces {
+                particles: vec![Vector3::zeros(); bodies.positions.len()],
+                strands: vec![Vector3::new(1000.0, 0.0, 0.0); bodies.strands.len()],
+            }
+        }
+
+        fn acts_through_strands(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn strands_break_under_generated_tension() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        let strands = sim.web.strands.len();
+        sim.step();
+        assert_eq!(sim.web.strands.len(), strands);
+
+        sim.force_generators.push(Box::new(Tension));
+        sim.step();
+        assert_eq!(sim.web.strands.len(), 2 * strands);
+    }
+
+    #[test]
+    fn bugs_are_blown_along_with_the_wind() {
+        // A bug in still air is blown along with the wind
+        let mut sim = Simulator::new(0.01, Spiderweb::new());
+        sim.gravity = Vector3::zeros();
+        sim.wind = Box::new(ConstantWind::new(Vector3::new(0.0, 0.0, 1.0)));
+        sim.add_bug(Vector3::zeros(), Vector3::zeros(), 1.0, f64::INFINITY);
+        for _ in 0..100 {
+            sim.step();
+        }
+        let velocity = sim.bugs[0].particle.velocity;
+        assert!(velocity.z > 0.3 && velocity.z < 1.0);
+    }
+
+    #[test]
+    fn strands_across_the_wind_catch_it() {
+        // One strand across the wind and one along it
+        let mut web = Spiderweb::new();
+        for position in [Vector3::zeros(), Vector3::new(0.0, 0.1, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(1.1, 0.0, 0.0)] {
+            web.push_particle(Particle::new(position, Vector3::zeros(), 1.0, false, ParticleType::Silk));
+        }
+        web.push_strand(SilkStrand::new(0, 1, 0.1, 1000.0, 10.0));
+        web.push_strand(SilkStrand::new(2, 3, 0.1, 1000.0, 10.0));
+        let mut sim = Simulator::new(0.01, web);
+        sim.wind = Box::new(ConstantWind::new(Vector3::new(0.1, 0.0, 0.0)));
+        sim.strand_drag = true;
+
+        let positions: Vec<Vector3<f64>> = sim.web.particles.iter().map(|p| p.position).collect();
+        let velocities = vec![Vector3::zeros(); positions.len()];
+        let forces = StrandDrag::new().forces(&sim, &sim.web_bodies(&positions, &velocities));
+        assert!(forces[0].x > 0.0 && forces[0].y == 0.0 && forces[0].z == 0.0);
+        assert_eq!(forces[0], forces[1]);
+        assert_eq!(forces[2], Vector3::zeros());
+        assert_eq!(forces[3], Vector3::zeros());
+
+        // Moving with the air, the strand feels nothing
+        let velocities = vec![Vector3::new(0.1, 0.0, 0.0); positions.len()];
+        let forces = StrandDrag::new().forces(&sim, &sim.web_bodies(&positions, &velocities));
+        assert_eq!(forces[0], Vector3::zeros());
+    }
+
+    #[test]
+    fn strand_drag_replaces_drag_on_silk_particles() {
+        let mut sim = Simulator::new(0.01, chain_web(1000.0));
+        sim.wind = Box::new(ConstantWind::new(Vector3::new(0.0, 0.0, 1.0)));
+        sim.add_bug(Vector3::new(0.55, 0.5, 0.0), Vector3::zeros(), 1.0, f64::INFINITY);
+        // As hard as the linear drag at the speed of the wind
+        sim.force_generators.push(Box::new(QuadraticDrag { coefficient: sim.drag_coefficient }));
+        let air_forces = |sim: &Simulator| {
+            let contributions = sim.force_contributions();
+            let total = |name: &str| contributions.iter().find(|c| c.name == name).unwrap().net_force;
+            (total("Linear Drag") + total("Wind") + total("Quadratic Drag"), total("Strand Drag"))
+        };
+
+        // The wind pushes on the 9 free silk particles and the bug
+        let (particle_drag, strand_drag) = air_forces(&sim);
+        assert_eq!(particle_drag, Vector3::new(0.0, 0.0, 20.0 * sim.drag_coefficient));
+        assert_eq!(strand_drag, Vector3::zeros());
+
+        // Now only the bug feels it, and the silk feels it through its strands
+        sim.strand_drag = true;
+        let (particle_drag, strand_drag) = air_forces(&sim);
+        assert_eq!(particle_drag, Vector3::new(0.0, 0.0, 2.0 * sim.drag_coefficient));
+        assert!(strand_drag.z > 0.0);
+    }
+
+    #[test]
+    fn silk_stiffens_then_breaks_at_its_failure_strain() {
+        let silk = SilkMaterial::MAJOR_AMPULLATE;
+        let strand = SilkStrand { material: silk, ..SilkStrand::new(0, 1, 0.1, 1000.0, 10.0) };
+        let len = |strain: f64| 0.1 * (1.0 + strain);
+        // The tension rises at the tangent stiffness on every segment of the curve
+        let stiffness: Vec<f64> = [0.01, 0.1, 0.15, 0.25].iter().map(|&strain| {
+            let rise = (strand.tension(len(strain) + 1e-7) - strand.tension(len(strain) - 1e-7)) / 2e-7;
+            assert!((rise - strand.tangent_stiffness(len(strain))).abs() < 1e-3);
+            strand.tangent_stiffness(len(strain))
+        }).collect();
+        // Softer while it yields, then stiffer near failure than it started
+        assert!(stiffness[1] < stiffness[0]);
+        assert!(stiffness[1] < stiffness[2] && stiffness[2] < stiffness[3]);
+        assert!(stiffness[3] > stiffness[0]);
+
+        let stretched = |strain: f64| {
+            let mut web = Spiderweb::new();
+            web.push_particle(Particle::new(Vector3::zeros(), Vector3::zeros(), 1.0, true, ParticleType::Silk));
+            web.push_particle(Particle::new(Vector3::new(0.1 * (1.0 + strain), 0.0, 0.0), Vector3::zeros(), 1.0, false, ParticleType::Silk));
+            web.push_strand(SilkStrand { material: silk, ..SilkStrand::new(0, 1, 0.1, 1000.0, 10.0) });
+            let mut sim = Simulator::new(0.001, web);
+            sim.step();
+            sim
+        };
+        // Holds until it gets to its failure strain, well past the force
+        // linear silk snaps at
+        assert_eq!(stretched(0.25).web.strands.len(), 1);
+        assert_eq!(stretched(0.3).web.strands.len(), 2);
+    }
+}
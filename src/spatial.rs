@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector3;
+
+type Cell = (i64, i64, i64);
+
+/// Items whose bounding box covers more cells than this are kept in a
+/// separate list that every query checks, rather than filling up the grid
+const MAX_CELLS_PER_STRAND: usize = 64;
+
+/// A uniform grid over items with a bounding box, such as strands or bugs.
+/// Each item is listed in every cell its bounding box overlaps, so any item
+/// passing through a region of space is found by looking at the cells
+/// covering that region.
+#[derive(Clone)]
+pub struct SpatialGrid {
+    pub cell_size: f64,
+    cells: HashMap<Cell, Vec<usize>>,
+    /// The cells each item is listed in, by item index
+    item_cells: Vec<Vec<Cell>>,
+    /// The box each item was filed under, by item index
+    item_boxes: Vec<(Vector3<f64>, Vector3<f64>)>,
+    oversized: Vec<usize>,
+    /// The lowest and highest cell ever filled since the grid was cleared
+    bounds: Option<(Cell, Cell)>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64) -> Self {
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            item_cells: Vec::new(),
+            item_boxes: Vec::new(),
+            oversized: Vec::new(),
+            bounds: None,
+        }
+    }
+
+    fn cell(&self, pos: Vector3<f64>) -> Cell {
+        (
+            (pos.x / self.cell_size).floor() as i64,
+            (pos.y / self.cell_size).floor() as i64,
+            (pos.z / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Every cell overlapping the box from `min` to `max`, or None if there
+    /// are more than `max_cells` of them
+    fn cells_in_box(&self, min: Vector3<f64>, max: Vector3<f64>, max_cells: usize) -> Option<Vec<Cell>> {
+        let (lo, hi) = (self.cell(min), self.cell(max));
+        let count = (hi.0 - lo.0 + 1) as f64 * (hi.1 - lo.1 + 1) as f64 * (hi.2 - lo.2 + 1) as f64;
+        // Also catches NaN positions, which would otherwise make an empty box
+        if count.is_nan() || count > max_cells as f64 {
+            return None;
+        }
+        let mut cells = Vec::with_capacity(count as usize);
+        for x in lo.0..=hi.0 {
+            for y in lo.1..=hi.1 {
+                for z in lo.2..=hi.2 {
+                    cells.push((x, y, z));
+                }
+            }
+        }
+        Some(cells)
+    }
+
+    /// Adds the item with index `item_idx`, which stays inside the box
+    /// from `min` to `max`. Items must be inserted in index order.
+    pub fn insert(&mut self, item_idx: usize, min: Vector3<f64>, max: Vector3<f64>) {
+        debug_assert_eq!(item_idx, self.item_cells.len());
+        self.item_cells.push(Vec::new());
+        self.item_boxes.push((min, max));
+        self.file(item_idx, min, max);
+    }
+
+    /// Lists the item in every cell the box overlaps, or with the oversized
+    /// items if it overlaps too many
+    fn file(&mut self, item_idx: usize, min: Vector3<f64>, max: Vector3<f64>) {
+        match self.cells_in_box(min, max, MAX_CELLS_PER_STRAND) {
+            Some(cells) => {
+                for &cell in &cells {
+                    self.cells.entry(cell).or_default().push(item_idx);
+                    let (lo, hi) = self.bounds.get_or_insert((cell, cell));
+                    *lo = (lo.0.min(cell.0), lo.1.min(cell.1), lo.2.min(cell.2));
+                    *hi = (hi.0.max(cell.0), hi.1.max(cell.1), hi.2.max(cell.2));
+                }
+                self.item_cells[item_idx] = cells;
+            }
+            None => self.oversized.push(item_idx),
+        }
+    }
+
+    /// Files the item at `item_idx` under the box from `min` to `max`
+    /// instead of wherever it was before, touching only the cells it leaves
+    /// and joins
+    pub fn update(&mut self, item_idx: usize, min: Vector3<f64>, max: Vector3<f64>) {
+        for cell in std::mem::take(&mut self.item_cells[item_idx]) {
+            if let Some(items) = self.cells.get_mut(&cell) {
+                items.retain(|&i| i != item_idx);
+                if items.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+        self.oversized.retain(|&i| i != item_idx);
+        self.item_boxes[item_idx] = (min, max);
+        self.file(item_idx, min, max);
+    }
+
+    /// Whether the box from `min` to `max` is inside the box the item at
+    /// `item_idx` is filed under, so queries are sure to find it there
+    pub fn holds(&self, item_idx: usize, min: Vector3<f64>, max: Vector3<f64>) -> bool {
+        let (filed_min, filed_max) = self.item_boxes[item_idx];
+        (0..3).all(|axis| filed_min[axis] <= min[axis] && max[axis] <= filed_max[axis])
+    }
+
+    /// Mirrors `Vec::swap_remove` on the items: takes out the item at
+    /// `item_idx` and gives its index to what was the last item
+    pub fn swap_remove(&mut self, item_idx: usize) {
+        let last_idx = self.item_cells.len() - 1;
+        let rename_last = |items: &mut Vec<usize>| {
+            for i in items.iter_mut() {
+                if *i == last_idx {
+                    *i = item_idx;
+                }
+            }
+        };
+        let replace = |items: &mut Vec<usize>| {
+            items.retain(|&i| i != item_idx);
+            rename_last(items);
+        };
+
+        self.item_boxes.swap_remove(item_idx);
+        let removed_cells = self.item_cells.swap_remove(item_idx);
+        for cell in &removed_cells {
+            if let Some(items) = self.cells.get_mut(cell) {
+                replace(items);
+                if items.is_empty() {
+                    self.cells.remove(cell);
+                }
+            }
+        }
+        // Cells the removed item shared with the moved one were renamed above
+        if item_idx != last_idx {
+            for cell in &self.item_cells[item_idx] {
+                if let Some(items) = self.cells.get_mut(cell) {
+                    rename_last(items);
+                }
+            }
+        }
+        replace(&mut self.oversized);
+    }
+
+    /// Number of items in the grid
+    pub fn item_count(&self) -> usize {
+        self.item_cells.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.item_cells.clear();
+        self.item_boxes.clear();
+        self.oversized.clear();
+        self.bounds = None;
+    }
+
+    /// Indices of every item that may pass through the box from `min` to
+    /// `max`, in increasing order. Items outside the box can be included too.
+    pub fn candidates(&self, min: Vector3<f64>, max: Vector3<f64>) -> Vec<usize> {
+        let mut candidates = self.oversized.clone();
+        match self.cells_in_box(min, max, self.cells.len().max(1)) {
+            Some(cells) => {
+                for cell in cells {
+                    if let Some(items) = self.cells.get(&cell) {
+                        candidates.extend_from_slice(items);
+                    }
+                }
+            }
+            // The box is bigger than the grid, so it's cheaper to visit every filled cell
+            None => {
+                for items in self.cells.values() {
+                    candidates.extend_from_slice(items);
+                }
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Whether the box from `min` to `max` holds every cell an item has
+    /// been listed in, so that `candidates` for it returns every item
+    pub fn covers(&self, min: Vector3<f64>, max: Vector3<f64>) -> bool {
+        let Some((lo, hi)) = self.bounds else {
+            return true;
+        };
+        let (min, max) = (self.cell(min), self.cell(max));
+        // Negated so that a NaN box counts as covering, ending any search
+        !(min.0 > lo.0 || min.1 > lo.1 || min.2 > lo.2 || max.0 < hi.0 || max.1 < hi.1 || max.2 < hi.2)
+    }
+}
@@ -1,6 +1,18 @@
 extern crate nalgebra as na;
 use na::Vector3;
 
+use crate::material::SilkMaterial;
+use crate::spatial::SpatialGrid;
+
+/// Size of the grid cells used to look up strands by position
+const STRAND_GRID_CELL_SIZE: f64 = 0.1;
+/// How far past its ends a strand is filed in the strand grid, so it can
+/// move this far before it has to be filed again
+const STRAND_GRID_MARGIN: f64 = 0.02;
+/// Shortest piece of silk kept as a strand of its own. Anything shorter is
+/// glue between threads, or a scrap too small to matter.
+pub const MIN_STRAND_LENGTH: f64 = 0.005;
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ParticleType {
     Bug,
@@ -41,6 +53,7 @@ pub struct SilkStrand {
     pub length: f64,
     pub stiffness: f64,
     pub damping: f64,
+    pub material: SilkMaterial,
 }
 
 impl SilkStrand {
@@ -50,15 +63,58 @@ impl SilkStrand {
             end,
             length,
             stiffness,
-            damping
+            damping,
+            material: SilkMaterial::LINEAR,
         }
     }
+
+    /// Rest length, kept above zero so strain stays finite
+    fn rest_length(&self) -> f64 {
+        self.length.max(1e-9)
+    }
+
+    /// The same silk cut to `length`. A shorter piece stretches less under the
+    /// same tension, so it's stiffer in proportion and breaks at the same
+    /// tension as the whole strand.
+    pub fn cut_to(&self, length: f64) -> SilkStrand {
+        SilkStrand { length, stiffness: self.stiffness * self.rest_length() / length.max(1e-9), ..*self }
+    }
+
+    /// How far the strand is stretched when it's `len` long, as a fraction
+    /// of its rest length
+    pub fn strain(&self, len: f64) -> f64 {
+        (len - self.length) / self.rest_length()
+    }
+
+    /// How hard the strand pulls when it's `len` long, or pushes if negative
+    pub fn tension(&self, len: f64) -> f64 {
+        self.stiffness * self.rest_length() * self.material.stress(self.strain(len))
+    }
+
+    /// How quickly the tension rises with length when the strand is `len` long
+    pub fn tangent_stiffness(&self, len: f64) -> f64 {
+        self.stiffness * self.material.slope(self.strain(len))
+    }
+
+    /// Energy stored in the strand when it's `len` long
+    pub fn elastic_energy(&self, len: f64) -> f64 {
+        self.stiffness * self.rest_length().powi(2) * self.material.energy(self.strain(len))
+    }
+
+    /// Whether the strand is stretched past the point its silk breaks at
+    pub fn is_overstretched(&self, len: f64) -> bool {
+        self.strain(len) > self.material.failure_strain
+    }
 }
 
 #[derive(Clone)]
 pub struct Spiderweb {
     pub particles: Vec<Particle>,
     pub strands: Vec<SilkStrand>,
+    /// Every strand, filed under a box a margin wider than where it is. Kept
+    /// in step with `strands` by the methods that add and remove strands, and
+    /// with the particles by `update_strand_grid`.
+    strand_grid: SpatialGrid,
 }
 
 impl Spiderweb {
@@ -66,6 +122,7 @@ impl Spiderweb {
         Spiderweb {
             particles: Vec::new(),
             strands: Vec::new(),
+            strand_grid: SpatialGrid::new(STRAND_GRID_CELL_SIZE),
         }
     }
 
@@ -82,7 +139,7 @@ impl Spiderweb {
     pub fn insert_particle_into_web(&mut self, particle : Particle, strand_idx : usize, preserve_length : bool) {
         self.push_particle(particle);
         let new_particle_idx = self.particles.len() - 1;
-        let strand = self.strands.swap_remove(strand_idx);
+        let strand = self.remove_strand(strand_idx);
         let start_particle = self.particles[strand.start];
         let end_particle = self.particles[strand.end];
 
@@ -98,38 +155,257 @@ impl Spiderweb {
             end_len = (particle.position - end_particle.position).magnitude();
         }
 
-        let new_start_strand = SilkStrand::new(strand.start, new_particle_idx, start_len, strand.stiffness, strand.damping);
-        let new_end_strand = SilkStrand::new(new_particle_idx, strand.end, end_len, strand.stiffness, strand.damping);
+        let new_start_strand = SilkStrand { end: new_particle_idx, length: start_len, ..strand };
+        let new_end_strand = SilkStrand { start: new_particle_idx, length: end_len, ..strand };
 
-        self.strands.push(new_start_strand);
-        self.strands.push(new_end_strand);
+        self.push_strand(new_start_strand);
+        self.push_strand(new_end_strand);
+    }
+
+    /// Splits the strand at `t`, from 0 at its start particle to 1 at its end
+    /// particle, and puts the particle at that point on it. The rest length is
+    /// split at `t` too, so the strand's tension doesn't change. The particle
+    /// keeps its velocity. Returns the index of the particle.
+    ///
+    /// * `particle`: The given particle to insert into the web
+    /// * `strand_idx`: The index of the strand the particle is being inserted into
+    /// * `t`: How far along the strand to insert the particle
+    pub fn insert_particle_at(&mut self, mut particle : Particle, strand_idx : usize, t : f64) -> usize {
+        let strand = self.remove_strand(strand_idx);
+        let point = self.particles[strand.start].position.lerp(&self.particles[strand.end].position, t);
+        // Move the verlet history along with the particle so its velocity stays the same
+        particle.prev_position += point - particle.position;
+        particle.position = point;
+
+        self.push_particle(particle);
+        let new_particle_idx = self.particles.len() - 1;
+        let start_len = strand.length * t;
+        self.push_strand(SilkStrand { end: new_particle_idx, length: start_len, ..strand });
+        self.push_strand(SilkStrand { start: new_particle_idx, length: strand.length - start_len, ..strand });
+        new_particle_idx
+    }
+
+    /// Snaps a strand in two at its midpoint. Each half stays attached to its
+    /// original particle and gets a new free end particle of its own, with half
+    /// of the old rest length and so twice its stiffness. A strand under
+    /// tension recoils, so each free end snaps back towards its particle until
+    /// its half is at rest. Silk stretched past its failure strain has yielded
+    /// all the way, so its halves are left slack at the midpoint instead.
+    /// Halves shorter than `MIN_STRAND_LENGTH` are dropped along with their
+    /// free ends.
+    ///
+    /// * `strand_idx`: The index of the strand to break
+    pub fn break_strand(&mut self, strand_idx : usize) {
+        let strand = self.remove_strand(strand_idx);
+        let start_particle = self.particles[strand.start];
+        let end_particle = self.particles[strand.end];
+        let len = (end_particle.position - start_particle.position).norm();
+        let half_len = if strand.is_overstretched(len) { len / 2.0 } else { strand.length / 2.0 };
+        if half_len < MIN_STRAND_LENGTH {
+            return;
+        }
+
+        // The two free ends share the average mass of the strand's particles
+        let tip_mass = (start_particle.mass + end_particle.mass) / 4.0;
+        let mut tip = Particle::new(
+            start_particle.position.lerp(&end_particle.position, 0.5),
+            start_particle.velocity.lerp(&end_particle.velocity, 0.5),
+            tip_mass,
+            false,
+            ParticleType::Silk,
+        );
+        // Keep the verlet history so the tips start with the strand's velocity
+        tip.prev_position = start_particle.prev_position.lerp(&end_particle.prev_position, 0.5);
+
+        // Each tip moves back from the midpoint by as much as its half is stretched
+        let stretch = (len / 2.0 - half_len).max(0.0);
+        let recoil = (end_particle.position - start_particle.position) * (stretch / len.max(1e-9));
+        let moved = |offset: Vector3<f64>| Particle {
+            position: tip.position + offset,
+            prev_position: tip.prev_position + offset,
+            ..tip
+        };
+
+        self.push_particle(moved(-recoil));
+        let start_tip_idx = self.particles.len() - 1;
+        self.push_particle(moved(recoil));
+        let end_tip_idx = self.particles.len() - 1;
+
+        let half = strand.cut_to(half_len);
+        self.push_strand(SilkStrand { end: start_tip_idx, ..half });
+        self.push_strand(SilkStrand { start: end_tip_idx, ..half });
     }
 
     /// Finds the closest strand to the given position by finding the smallest
     /// distance to the silk strand using projection. 
     /// 
-    /// Time complexity of O(# of strands)
+    /// Searches the strand grid in a box around the position that doubles in
+    /// size until the closest strand found is certain to be inside it, so
+    /// only strands near the position are usually looked at. Ties go to the
+    /// lowest index.
     pub fn get_closest_strand(&self, pos : Vector3<f64>) -> usize {
-        let mut closest_strand_dist = f64::INFINITY;
-        let mut closest_strand_idx = 0;
-        for (idx, strand) in self.strands.iter().enumerate(){
-            let p_start = self.particles[strand.start].position;
-            let p_end = self.particles[strand.end].position;
-
-            let v = p_end - p_start;
-            let w = pos - p_start;
+        if !self.strand_grid_in_sync() {
+            return self.closest_of(pos, 0..self.strands.len()).0;
+        }
 
-            let t = w.dot(&v) / v.dot(&v);
-            let t_clamped = t.clamp(0.0, 1.0);
+        let mut half_width = self.strand_grid.cell_size;
+        loop {
+            let (min, max) = (pos.add_scalar(-half_width), pos.add_scalar(half_width));
+            let (closest_strand_idx, closest_strand_dist) = self.closest_of(pos, self.strand_grid.candidates(min, max));
+            // Anything closer than the box's half width passes through the box
+            if closest_strand_dist <= half_width || self.strand_grid.covers(min, max) {
+                return closest_strand_idx;
+            }
+            half_width *= 2.0;
+        }
+    }
 
-            let projection = p_start + v * t_clamped;
-            let distance = (pos - projection).magnitude();
+    /// The closest of the given strands to the position and its distance,
+    /// with ties going to the strand that comes first
+    fn closest_of(&self, pos : Vector3<f64>, strand_idxs : impl IntoIterator<Item = usize>) -> (usize, f64) {
+        let mut closest_strand_dist = f64::INFINITY;
+        let mut closest_strand_idx = 0;
+        for idx in strand_idxs {
+            let distance = self.distance_to_strand(pos, idx);
             if distance < closest_strand_dist {
                 closest_strand_dist = distance;
                 closest_strand_idx = idx;
             }
         }
-        closest_strand_idx
+        (closest_strand_idx, closest_strand_dist)
+    }
+
+    /// Distance from the position to the nearest point on the strand
+    pub fn distance_to_strand(&self, pos : Vector3<f64>, strand_idx : usize) -> f64 {
+        let strand = &self.strands[strand_idx];
+        let p_start = self.particles[strand.start].position;
+        let p_end = self.particles[strand.end].position;
+
+        let v = p_end - p_start;
+        let w = pos - p_start;
+
+        let t = w.dot(&v) / v.dot(&v);
+        let t_clamped = t.clamp(0.0, 1.0);
+
+        let projection = p_start + v * t_clamped;
+        (pos - projection).magnitude()
+    }
+
+    /// Indices of the strands that may pass through the box from `min` to
+    /// `max`, in increasing order, going by where the strands were when the
+    /// strand grid was last updated
+    pub fn strands_in_box(&self, min : Vector3<f64>, max : Vector3<f64>) -> Vec<usize> {
+        if !self.strand_grid_in_sync() {
+            return (0..self.strands.len()).collect();
+        }
+        self.strand_grid.candidates(min, max)
+    }
+
+    /// Everywhere the strand passed through since its particles' previous
+    /// positions, so swept collision tests find it too
+    fn strand_box(&self, strand : &SilkStrand) -> (Vector3<f64>, Vector3<f64>) {
+        let (start, end) = (&self.particles[strand.start], &self.particles[strand.end]);
+        let min = start.position.inf(&start.prev_position).inf(&end.position).inf(&end.prev_position);
+        let max = start.position.sup(&start.prev_position).sup(&end.position).sup(&end.prev_position);
+        (min, max)
+    }
+
+    /// Files the strand at `strand_idx` under its box in the strand grid,
+    /// widened by the margin
+    fn file_strand(&mut self, strand_idx : usize) {
+        let (min, max) = self.strand_box(&self.strands[strand_idx]);
+        let (min, max) = (min.add_scalar(-STRAND_GRID_MARGIN), max.add_scalar(STRAND_GRID_MARGIN));
+        if strand_idx < self.strand_grid.item_count() {
+            self.strand_grid.update(strand_idx, min, max);
+        } else {
+            self.strand_grid.insert(strand_idx, min, max);
+        }
+    }
+
+    /// Files again every strand that has moved out of the box it was filed
+    /// under in the strand grid. Needs to be called after the particles move
+    /// for strand lookups to stay accurate. Strands are filed with a margin,
+    /// so most steps only a few strands have to be moved.
+    pub fn update_strand_grid(&mut self) {
+        if !self.strand_grid_in_sync() {
+            self.refresh_strand_grid();
+            return;
+        }
+        for idx in 0..self.strands.len() {
+            let (min, max) = self.strand_box(&self.strands[idx]);
+            if !self.strand_grid.holds(idx, min, max) {
+                self.file_strand(idx);
+            }
+        }
+    }
+
+    /// Rebuilds the whole strand grid from where the particles are now
+    pub fn refresh_strand_grid(&mut self) {
+        self.strand_grid.clear();
+        for idx in 0..self.strands.len() {
+            self.file_strand(idx);
+        }
+    }
+
+    /// Whether the strand grid has one entry per strand. It won't if strands
+    /// were added or removed without going through `Spiderweb`.
+    fn strand_grid_in_sync(&self) -> bool {
+        self.strand_grid.item_count() == self.strands.len()
+    }
+
+    /// Middle of the box around every particle, or the origin for an empty web
+    pub fn centre(&self) -> Vector3<f64> {
+        let mut positions = self.particles.iter().map(|p| p.position);
+        let Some(first) = positions.next() else {
+            return Vector3::zeros();
+        };
+        let (min, max) = positions.fold((first, first), |(min, max), pos| (min.inf(&pos), max.sup(&pos)));
+        (min + max) / 2.0
+    }
+
+    /// Kinetic energy of the moving particles plus the elastic energy stored
+    /// in the strands
+    pub fn energy(&self) -> f64 {
+        let kinetic: f64 = self.particles.iter()
+            .filter(|p| !p.fixed)
+            .map(|p| 0.5 * p.mass * p.velocity.norm_squared())
+            .sum();
+        let elastic: f64 = self.strands.iter()
+            .map(|strand| {
+                let len = (self.particles[strand.start].position - self.particles[strand.end].position).magnitude();
+                strand.elastic_energy(len)
+            })
+            .sum();
+        kinetic + elastic
+    }
+
+    /// Indices of the strands on each particle, found in one pass over the strands
+    pub fn strands_by_particle(&self) -> Vec<Vec<usize>> {
+        let mut strands_on = vec![Vec::new(); self.particles.len()];
+        for (idx, strand) in self.strands.iter().enumerate() {
+            strands_on[strand.start].push(idx);
+            if strand.end != strand.start {
+                strands_on[strand.end].push(idx);
+            }
+        }
+        strands_on
+    }
+
+    /// Takes out the particle at `particle_idx`, moving the last particle into
+    /// its place like `Vec::swap_remove` and pointing its strands at its new
+    /// index. The particle must not have any strands left.
+    pub fn remove_particle(&mut self, particle_idx : usize) -> Particle {
+        debug_assert!(self.strands.iter().all(|s| s.start != particle_idx && s.end != particle_idx));
+        let last_idx = self.particles.len() - 1;
+        for strand in &mut self.strands {
+            if strand.start == last_idx {
+                strand.start = particle_idx;
+            }
+            if strand.end == last_idx {
+                strand.end = particle_idx;
+            }
+        }
+        self.particles.swap_remove(particle_idx)
     }
 
     pub fn push_particle(&mut self, particle : Particle) {
@@ -137,6 +413,94 @@ impl Spiderweb {
     }
 
     pub fn push_strand(&mut self, strand : SilkStrand) {
+        let in_sync = self.strand_grid_in_sync();
         self.strands.push(strand);
+        if in_sync {
+            self.file_strand(self.strands.len() - 1);
+        }
     }
-}
\ No newline at end of file
+
+    /// Takes out the strand at `strand_idx`, moving the last strand into its
+    /// place like `Vec::swap_remove`
+    pub fn remove_strand(&mut self, strand_idx : usize) -> SilkStrand {
+        if !self.strand_grid_in_sync() {
+            self.refresh_strand_grid();
+        }
+        self.strand_grid.swap_remove(strand_idx);
+        self.strands.swap_remove(strand_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain_web(stiffness: f64) -> Spiderweb {
+        let mut web = Spiderweb::new();
+        let count = 10;
+        for i in 0..=count {
+            let position = Vector3::new(i as f64 / count as f64, 0.0, 0.0);
+            let fixed = i == 0 || i == count;
+            web.push_particle(Particle::new(position, Vector3::zeros(), 1.0, fixed, ParticleType::Silk));
+        }
+        for i in 0..count {
+            web.push_strand(SilkStrand::new(i, i + 1, 0.1, stiffness, 10.0));
+        }
+        web
+    }
+
+    #[test]
+    fn broken_strands_recoil_from_new_free_ends() {
+        let mut web = chain_web(1000.0);
+        web.particles[5].velocity = Vector3::new(0.0, 0.0, 1.0);
+        web.particles[6].mass = 3.0;
+        web.strands[5].material = SilkMaterial::MAJOR_AMPULLATE;
+        let (particles, strands) = (web.particles.len(), web.strands.len());
+        web.break_strand(5);
+
+        assert_eq!(web.particles.len(), particles + 2);
+        assert_eq!(web.strands.len(), strands + 1);
+        let (start_tip, end_tip) = (particles, particles + 1);
+        for tip in &web.particles[start_tip..] {
+            assert_eq!(tip.position, Vector3::new(0.55, 0.0, 0.0));
+            assert_eq!(tip.velocity, Vector3::new(0.0, 0.0, 0.5));
+            assert_eq!(tip.mass, 1.0);
+            assert!(!tip.fixed && tip.particle_type == ParticleType::Silk);
+        }
+        // Each half keeps one of the old ends, its silk and half the rest length
+        let half = |start: usize, end: usize| web.strands.iter().find(|s| s.start == start && s.end == end).unwrap();
+        for half in [half(5, start_tip), half(end_tip, 6)] {
+            assert_eq!(half.length, 0.05);
+            assert_eq!(half.material, SilkMaterial::MAJOR_AMPULLATE);
+        }
+        assert!(web.strands.iter().all(|s| !(s.start == 5 && s.end == 6)));
+
+        // A stretched strand's free ends snap back until their halves are at rest
+        let mut web = chain_web(1000.0);
+        web.particles[6].position.x += 0.02;
+        web.break_strand(5);
+        let tips = &web.particles[particles..];
+        assert!((tips[0].position.x - 0.55).abs() < 1e-9 && (tips[1].position.x - 0.57).abs() < 1e-9);
+        assert!(web.strands[strands - 1..].iter().all(|half| half.stiffness == 2000.0));
+
+        // Scraps too short to keep go without leaving free ends behind
+        let mut web = chain_web(1000.0);
+        web.strands[5].length = 0.008;
+        web.break_strand(5);
+        assert_eq!((web.particles.len(), web.strands.len()), (particles, strands - 1));
+    }
+
+    #[test]
+    fn cut_strands_break_at_the_same_tension() {
+        let silk = SilkMaterial::MAJOR_AMPULLATE;
+        let long = SilkStrand { material: silk, ..SilkStrand::new(0, 1, 0.1, 1000.0, 10.0) };
+        let short = long.cut_to(0.01);
+        assert!((short.stiffness - 10.0 * long.stiffness).abs() < 1e-9);
+        // Stretched by the same fraction, both pull just as hard and break together
+        let tension = |strand: &SilkStrand, strain: f64| strand.tension(strand.length * (1.0 + strain));
+        for strain in [0.01, 0.1, 0.2, silk.failure_strain] {
+            assert!((tension(&short, strain) - tension(&long, strain)).abs() < 1e-9);
+        }
+        assert!(!short.is_overstretched(0.01 * 1.26) && short.is_overstretched(0.01 * 1.28));
+    }
+}
@@ -1,8 +1,14 @@
 use nalgebra::Vector3;
 use rand::{random, thread_rng, Rng};
 
-use crate::web::{Particle, SilkStrand, Spiderweb, ParticleType};
+use crate::material::SilkMaterial;
+use crate::web::{Particle, SilkStrand, Spiderweb, ParticleType, MIN_STRAND_LENGTH};
 
+/// Length of strand `Webgen::stiffness` is the stiffness of. Shorter strands
+/// of the same silk stretch less under the same tension, so they're stiffer
+/// in proportion. This is short enough that the stiffest strands, at
+/// `MIN_STRAND_LENGTH`, stay stable for explicit integrators at a 0.01 timestep.
+const SPAN_LENGTH: f64 = 0.025;
 
 /* List of genes:
 * Gene 01 - Number of first radii
@@ -41,8 +47,13 @@ pub struct Webgen {
     /// A list of all radial (capture) points from the center to the end
     radial_points : Vec<usize>,
     pub mass : f64,
+    /// Stiffness of a strand `SPAN_LENGTH` long
     pub stiffness : f64,
     pub damping : f64,
+    /// Silk of the frame and the radii
+    pub radial_material : SilkMaterial,
+    /// Silk of the capture spiral
+    pub spiral_material : SilkMaterial,
 }
 
 impl Webgen {
@@ -67,6 +78,8 @@ impl Webgen {
             mass : 1.0,
             stiffness : 1000.0,
             damping : 10.0,
+            radial_material : SilkMaterial::MAJOR_AMPULLATE,
+            spiral_material : SilkMaterial::FLAGELLIFORM,
         }
     }
     fn new_particle(&mut self, pos : Vector3<f64>) -> usize {
@@ -79,9 +92,9 @@ impl Webgen {
         (self.web.particles[a].position - self.web.particles[b].position).norm()
     }
 
-    fn new_strand(&mut self, a : usize, b : usize) -> usize {
+    fn new_strand(&mut self, a : usize, b : usize, material : SilkMaterial) -> usize {
         let len = self.get_len(a, b);
-        let strand = SilkStrand::new(a, b, len, self.stiffness, self.damping);
+        let strand = SilkStrand { material, ..SilkStrand::new(a, b, len, self.stiffness, self.damping) };
         self.web.push_strand(strand);
         self.web.strands.len() - 1
     }
@@ -108,7 +121,7 @@ impl Webgen {
     fn new_base_strand(&mut self, b: usize) {
         let further_particle = self.new_particle(self.web.particles[b].position * 2.0);
         self.web.particles[further_particle].fixed = true;
-        self.new_strand(b, further_particle);
+        self.new_strand(b, further_particle, self.radial_material);
     }
 
     /// Initial radii and frame construction
@@ -132,18 +145,18 @@ impl Webgen {
             let pos = Vector3::new(x, y, 0.0);
             let particle = self.new_particle(pos);
             let len = self.get_len(center, particle);
-            let strand = SilkStrand::new(center, particle, len, self.stiffness, self.damping);
+            let strand = SilkStrand { material: self.radial_material, ..SilkStrand::new(center, particle, len, self.stiffness, self.damping) };
             self.web.push_strand(strand);
             self.base_radii.push(particle);
             if i > 0 {
-                self.new_strand(prev_particle, particle);
+                self.new_strand(prev_particle, particle, self.radial_material);
             } else {
                 // When i is at 0, we need to connect the last particle to the first
                 start_particle = particle;
             }
             prev_particle = particle;
         }
-        self.new_strand(prev_particle, start_particle);
+        self.new_strand(prev_particle, start_particle, self.radial_material);
     }
 
     fn angle_btwn_points(&self, x : usize, y: usize) -> f64 {
@@ -190,7 +203,7 @@ impl Webgen {
                 let particle = self.new_particle(new_pos);
                 self.all_radii.push(particle);
                 let len = self.get_len(0, particle);
-                let strand = SilkStrand::new(0, particle, len, self.stiffness, self.damping);
+                let strand = SilkStrand { material: self.radial_material, ..SilkStrand::new(0, particle, len, self.stiffness, self.damping) };
 
                 let closest_strand = self.web.get_closest_strand(new_pos);
                 self.web.insert_particle_into_web(self.web.particles[particle], closest_strand, true);
@@ -215,7 +228,7 @@ impl Webgen {
             let closest_strand = self.web.get_closest_strand(point);
             self.web.insert_particle_into_web(self.web.particles[particle], closest_strand, false);
             if indx > 0 {
-                self.new_strand(particle, particle-1);
+                self.new_strand(particle, particle-1, self.spiral_material);
             }
             self.radial_points.push(particle);
             radii_magnitude += self.genes.radial_point_offset;
@@ -263,7 +276,7 @@ impl Webgen {
 
             let closest_strand = self.web.get_closest_strand(new_pos);
             self.web.insert_particle_into_web(self.web.particles[particle], closest_strand, false);
-            self.new_strand(particle, particle - 1);
+            self.new_strand(particle, particle - 1, self.spiral_material);
             
             last_dist = new_dist;
             last_dist_particle_indx += sign;
@@ -279,9 +292,44 @@ impl Webgen {
         self.stage_2();
         self.stage_3();
         self.stage_4();
+        self.weld_glued_threads();
+        for strand in &mut self.web.strands {
+            strand.stiffness = self.stiffness * SPAN_LENGTH / strand.length;
+        }
         self.web.clone()
     }
 
+    /// Merges the two ends of every strand shorter than `MIN_STRAND_LENGTH` into
+    /// one particle, leaving the other strands on it as stretched as they were
+    fn weld_glued_threads(&mut self) {
+        while let Some(glue_idx) = self.web.strands.iter().position(|strand| strand.length < MIN_STRAND_LENGTH) {
+            let glue = self.web.remove_strand(glue_idx);
+            let (kept, welded) = (glue.start, glue.end);
+            if kept == welded {
+                continue;
+            }
+            let welded_particle = self.web.particles[welded];
+            self.web.particles[kept].mass += welded_particle.mass;
+            self.web.particles[kept].fixed |= welded_particle.fixed;
+
+            let particles = &self.web.particles;
+            for strand in &mut self.web.strands {
+                let start = if strand.start == welded { kept } else { strand.start };
+                let end = if strand.end == welded { kept } else { strand.end };
+                let old_len = (particles[strand.start].position - particles[strand.end].position).norm();
+                strand.length += (particles[start].position - particles[end].position).norm() - old_len;
+                strand.start = start;
+                strand.end = end;
+            }
+            // Any other strand between the two ends goes with the glue
+            while let Some(loop_idx) = self.web.strands.iter().position(|strand| strand.start == strand.end) {
+                self.web.remove_strand(loop_idx);
+            }
+            self.web.remove_particle(welded);
+        }
+        self.web.refresh_strand_grid();
+    }
+
     pub fn simple_web(&mut self) -> Spiderweb {
         self.web = Spiderweb::new();
 
@@ -0,0 +1,432 @@
+use std::error::Error;
+
+use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::bug::random_unit_vector;
+
+/// Velocity of the air around the web. The simulator's `Wind` force
+/// generator drags particles along with it.
+pub trait WindField: Send + Sync {
+    /// Velocity of the air at `position` and `time`
+    fn velocity(&self, position: Vector3<f64>, time: f64) -> Vector3<f64>;
+
+    /// Called at the start of every step with the time the step ends at, for
+    /// fields that change in ways that can't be worked out from the time alone
+    fn advance(&mut self, _time: f64) {}
+}
+
+/// The same wind everywhere, all the time
+pub struct ConstantWind {
+    pub velocity: Vector3<f64>,
+}
+
+impl ConstantWind {
+    pub fn new(velocity: Vector3<f64>) -> Self {
+        ConstantWind { velocity }
+    }
+
+    pub fn calm() -> Self {
+        Self::new(Vector3::zeros())
+    }
+}
+
+impl WindField for ConstantWind {
+    fn velocity(&self, _position: Vector3<f64>, _time: f64) -> Vector3<f64> {
+        self.velocity
+    }
+}
+
+/// Wind that sways back and forth, blowing harder higher up
+pub struct SwayingWind {
+    pub strength: f64,
+}
+
+impl WindField for SwayingWind {
+    fn velocity(&self, position: Vector3<f64>, time: f64) -> Vector3<f64> {
+        let wind_dir = Vector3::new(0.8 * time.sin(), 0.05 * (time * 0.1).sin(), 0.1 * (time * 0.3).sin());
+        wind_dir * (position.y * self.strength)
+    }
+}
+
+/// Wind that blows in a loop, blowing harder closer to the z-axis
+pub struct LoopyWind {
+    pub strength: f64,
+}
+
+impl WindField for LoopyWind {
+    fn velocity(&self, position: Vector3<f64>, _time: f64) -> Vector3<f64> {
+        let z_pos = position.z.max(0.1);
+        let wind_dir = Vector3::new(position.y / z_pos, -position.x / z_pos, position.z / 4.0);
+        wind_dir * self.strength
+    }
+}
+
+/// Wind slowed by the ground, following the power law profile of the
+/// atmosphere's boundary layer. There's no wind at or below a height of zero.
+pub struct ShearWind {
+    /// Wind at `reference_height`
+    pub velocity: Vector3<f64>,
+    pub reference_height: f64,
+    /// How quickly the wind picks up with height. Around 0.14 over open
+    /// ground, and 0.3 or more among trees and buildings.
+    pub exponent: f64,
+}
+
+impl WindField for ShearWind {
+    fn velocity(&self, position: Vector3<f64>, _time: f64) -> Vector3<f64> {
+        if position.y <= 0.0 {
+            return Vector3::zeros();
+        }
+        self.velocity * (position.y / self.reference_height).powf(self.exponent)
+    }
+}
+
+/// Gradient noise over space, smooth and repeating every 256 units
+struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    fn new<R: Rng>(rng: &mut R) -> Self {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(rng);
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> u8 {
+        let p = &self.permutation;
+        p[p[p[x] as usize + y] as usize + z]
+    }
+
+    /// Dot product of the offset with one of the 12 gradients along the
+    /// diagonals of a cube's edges, picked by the hash
+    fn gradient(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    }
+
+    fn noise(&self, point: Vector3<f64>) -> f64 {
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |t: f64, a: f64, b: f64| a + t * (b - a);
+
+        let floor = point.map(f64::floor);
+        let [x, y, z] = [0, 1, 2].map(|i| floor[i].rem_euclid(256.0) as usize);
+        let f = point - floor;
+        let [u, v, w] = [0, 1, 2].map(|i| fade(f[i]));
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let hash = self.hash(x + dx, y + dy, z + dz);
+            Self::gradient(hash, f.x - dx as f64, f.y - dy as f64, f.z - dz as f64)
+        };
+
+        lerp(w,
+            lerp(v, lerp(u, corner(0, 0, 0), corner(1, 0, 0)), lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+            lerp(v, lerp(u, corner(0, 0, 1), corner(1, 0, 1)), lerp(u, corner(0, 1, 1), corner(1, 1, 1))),
+        )
+    }
+}
+
+/// Step used for the finite differences of the noise, in units of the
+/// length scale
+const CURL_STEP: f64 = 1e-4;
+
+/// Where each frame of turbulence is read from the noise, so frames look
+/// nothing alike
+const FRAME_OFFSET: Vector3<f64> = Vector3::new(37.1, 91.7, 53.3);
+
+/// Turbulent eddies on top of a mean wind. The eddies are the curl of
+/// Perlin noise, so the air neither bunches up nor thins out anywhere, and
+/// they change into new ones over `time_scale`.
+pub struct TurbulentWind {
+    pub mean: Vector3<f64>,
+    /// Typical speed of the eddies
+    pub intensity: f64,
+    /// Typical size of the eddies
+    pub length_scale: f64,
+    /// How long an eddy lasts
+    pub time_scale: f64,
+    /// One noise for each component of the potential the eddies are the curl of
+    potential: [Perlin; 3],
+}
+
+impl TurbulentWind {
+    pub fn new(mean: Vector3<f64>, intensity: f64, length_scale: f64, time_scale: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        TurbulentWind {
+            mean,
+            intensity,
+            length_scale,
+            time_scale,
+            potential: [Perlin::new(&mut rng), Perlin::new(&mut rng), Perlin::new(&mut rng)],
+        }
+    }
+
+    /// Curl of the potential at `point`, in the noise's own units
+    fn curl(&self, point: Vector3<f64>) -> Vector3<f64> {
+        // derivatives[i][j] is the derivative of component i along axis j
+        let derivatives = self.potential.each_ref().map(|noise| {
+            [Vector3::x(), Vector3::y(), Vector3::z()].map(|axis| {
+                let offset = axis * CURL_STEP;
+                (noise.noise(point + offset) - noise.noise(point - offset)) / (2.0 * CURL_STEP)
+            })
+        });
+        Vector3::new(
+            derivatives[2][1] - derivatives[1][2],
+            derivatives[0][2] - derivatives[2][0],
+            derivatives[1][0] - derivatives[0][1],
+        )
+    }
+
+    /// The eddies alone, without the mean wind
+    pub fn eddies(&self, position: Vector3<f64>, time: f64) -> Vector3<f64> {
+        let point = position / self.length_scale;
+        // Blend smoothly from one frame of eddies to the next. A blend of
+        // curls is still a curl, so the air still doesn't bunch up.
+        let frame = (time / self.time_scale).floor();
+        let t = time / self.time_scale - frame;
+        let blend = t * t * (3.0 - 2.0 * t);
+        let current = self.curl(point + FRAME_OFFSET * frame);
+        let next = self.curl(point + FRAME_OFFSET * (frame + 1.0));
+        current.lerp(&next, blend) * self.intensity
+    }
+}
+
+impl WindField for TurbulentWind {
+    fn velocity(&self, position: Vector3<f64>, time: f64) -> Vector3<f64> {
+        self.mean + self.eddies(position, time)
+    }
+}
+
+/// A gust that swells and dies away again everywhere at once
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Gust {
+    start: f64,
+    duration: f64,
+    peak: Vector3<f64>,
+}
+
+impl Gust {
+    fn velocity(&self, time: f64) -> Vector3<f64> {
+        let t = (time - self.start) / self.duration;
+        if !(0.0..=1.0).contains(&t) {
+            return Vector3::zeros();
+        }
+        self.peak * (std::f64::consts::PI * t).sin().powi(2)
+    }
+}
+
+/// A steady wind with gusts on top. Gusts come independently of each other
+/// at `gust_rate` a second on average, and blow roughly the way the steady
+/// wind does, or any way at all if there's none.
+pub struct GustyWind {
+    pub mean: Vector3<f64>,
+    pub gust_rate: f64,
+    /// How long a gust takes to swell and die away
+    pub gust_duration: f64,
+    /// Typical speed a gust adds at its peak
+    pub gust_strength: f64,
+    rng: StdRng,
+    gusts: Vec<Gust>,
+    next_gust: f64,
+}
+
+impl GustyWind {
+    pub fn new(mean: Vector3<f64>, gust_rate: f64, gust_duration: f64, gust_strength: f64, seed: u64) -> Self {
+        let mut wind = GustyWind {
+            mean,
+            gust_rate,
+            gust_duration,
+            gust_strength,
+            rng: StdRng::seed_from_u64(seed),
+            gusts: Vec::new(),
+            next_gust: 0.0,
+        };
+        wind.next_gust = wind.interval();
+        wind
+    }
+
+    /// Time from one gust to the next, exponentially distributed
+    fn interval(&mut self) -> f64 {
+        if self.gust_rate <= 0.0 {
+            return f64::INFINITY;
+        }
+        -(1.0 - self.rng.gen::<f64>()).ln() / self.gust_rate
+    }
+}
+
+impl WindField for GustyWind {
+    fn velocity(&self, _position: Vector3<f64>, time: f64) -> Vector3<f64> {
+        self.gusts.iter().fold(self.mean, |velocity, gust| velocity + gust.velocity(time))
+    }
+
+    fn advance(&mut self, time: f64) {
+        self.gusts.retain(|gust| gust.start + gust.duration >= time);
+        while self.next_gust <= time {
+            let direction = self.mean.try_normalize(1e-9).unwrap_or_else(|| random_unit_vector(&mut self.rng));
+            let wobble = random_unit_vector(&mut self.rng) * 0.3;
+            let peak = (direction + wobble).normalize() * (self.gust_strength * self.rng.gen_range(0.5..1.5));
+            self.gusts.push(Gust { start: self.next_gust, duration: self.gust_duration, peak });
+            self.next_gust += self.interval();
+        }
+    }
+}
+
+/// Wind measured at one point at a moment in time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WindSample {
+    pub time: f64,
+    pub velocity: Vector3<f64>,
+}
+
+/// Wind played back from a recording, such as an anemometer's log, linearly
+/// interpolated between samples. Before the first sample and after the last
+/// the wind holds steady, unless the recording loops.
+pub struct RecordedWind {
+    samples: Vec<WindSample>,
+    pub looping: bool,
+    /// Where the recording was made. When set, the recording is taken to be
+    /// turbulence frozen into the air and carried past that point by the
+    /// mean wind, as in Taylor's hypothesis, so gusts sweep across the web
+    /// rather than hitting all of it at once.
+    pub frozen_turbulence: Option<Vector3<f64>>,
+    /// Average wind over the whole recording
+    mean: Vector3<f64>,
+}
+
+impl RecordedWind {
+    pub fn new(mut samples: Vec<WindSample>) -> Self {
+        samples.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let duration = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        };
+        let mean = if duration > 0.0 {
+            // Trapezoidal rule, so unevenly spaced samples are weighted by
+            // how long they stand for
+            samples.windows(2).map(|pair| (pair[0].velocity + pair[1].velocity) * (0.5 * (pair[1].time - pair[0].time))).sum::<Vector3<f64>>() / duration
+        } else {
+            samples.first().map_or_else(Vector3::zeros, |sample| sample.velocity)
+        };
+        RecordedWind {
+            samples,
+            looping: false,
+            frozen_turbulence: None,
+            mean,
+        }
+    }
+
+    pub fn mean(&self) -> Vector3<f64> {
+        self.mean
+    }
+
+    /// The recorded wind at `time`
+    pub fn at(&self, time: f64) -> Vector3<f64> {
+        let (Some(first), Some(last)) = (self.samples.first(), self.samples.last()) else {
+            return Vector3::zeros();
+        };
+        let duration = last.time - first.time;
+        let time = if self.looping && duration > 0.0 {
+            first.time + (time - first.time).rem_euclid(duration)
+        } else {
+            time
+        };
+
+        let next = self.samples.partition_point(|sample| sample.time <= time);
+        if next == 0 {
+            return first.velocity;
+        }
+        if next == self.samples.len() {
+            return last.velocity;
+        }
+        let (before, after) = (self.samples[next - 1], self.samples[next]);
+        let t = (time - before.time) / (after.time - before.time);
+        before.velocity.lerp(&after.velocity, t)
+    }
+}
+
+impl WindField for RecordedWind {
+    fn velocity(&self, position: Vector3<f64>, time: f64) -> Vector3<f64> {
+        match self.frozen_turbulence {
+            // Air downwind of the recording point passed it earlier
+            Some(origin) if self.mean.norm_squared() > 0.0 => {
+                self.at(time - (position - origin).dot(&self.mean) / self.mean.norm_squared())
+            }
+            _ => self.at(time),
+        }
+    }
+}
+
+/// Reads a wind recording from a CSV file with a header row and the columns
+/// time, vx, vy, vz
+pub fn read_wind_log(path: &str) -> Result<Vec<WindSample>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut samples = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let values = record.iter().map(|field| field.trim().parse::<f64>()).collect::<Result<Vec<f64>, _>>()?;
+        let [time, vx, vy, vz] = values[..] else {
+            return Err(format!("expected 4 columns but found {}", values.len()).into());
+        };
+        samples.push(WindSample { time, velocity: Vector3::new(vx, vy, vz) });
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shear_wind_picks_up_with_height() {
+        // No wind at the ground, picking up with height
+        let shear = ShearWind { velocity: Vector3::new(1.0, 0.0, 0.0), reference_height: 1.0, exponent: 0.14 };
+        assert_eq!(shear.velocity(Vector3::zeros(), 0.0), Vector3::zeros());
+        assert!(shear.velocity(Vector3::new(0.0, 2.0, 0.0), 0.0).x > 1.0);
+    }
+
+    #[test]
+    fn turbulence_repeats_and_keeps_the_air_even() {
+        // Turbulence repeats for the same seed, changes over time, and
+        // neither bunches the air up nor thins it out
+        let turbulence = TurbulentWind::new(Vector3::zeros(), 1.0, 0.2, 1.0, 7);
+        let point = Vector3::new(0.13, 0.27, -0.4);
+        let same_seed = TurbulentWind::new(Vector3::zeros(), 1.0, 0.2, 1.0, 7);
+        assert_eq!(turbulence.velocity(point, 0.3), same_seed.velocity(point, 0.3));
+        assert_ne!(turbulence.velocity(point, 0.3), turbulence.velocity(point, 0.8));
+        let derivative = |axis: usize, component: usize| {
+            let offset = Vector3::ith(axis, 1e-4);
+            (turbulence.velocity(point + offset, 0.3)[component] - turbulence.velocity(point - offset, 0.3)[component]) / 2e-4
+        };
+        let divergence: f64 = (0..3).map(|axis| derivative(axis, axis)).sum();
+        assert!(divergence.abs() < 1e-4);
+        assert!((0..3).any(|axis| derivative(axis, (axis + 1) % 3).abs() > 0.1));
+    }
+
+    #[test]
+    fn recorded_wind_plays_back() {
+        let sample = |time, vx| WindSample { time, velocity: Vector3::new(vx, 0.0, 0.0) };
+        let mut wind = RecordedWind::new(vec![sample(1.0, 1.0), sample(3.0, 3.0), sample(2.0, 1.0)]);
+        assert_eq!(wind.mean(), Vector3::new(1.5, 0.0, 0.0));
+        assert_eq!(wind.at(1.5), Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(wind.at(2.5), Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(wind.at(5.0), Vector3::new(3.0, 0.0, 0.0));
+        wind.looping = true;
+        assert_eq!(wind.at(3.5), Vector3::new(1.0, 0.0, 0.0));
+
+        // Frozen turbulence reaches points downwind of where it was recorded later
+        let recorded_at = Vector3::new(0.5, 1.0, 0.0);
+        wind.frozen_turbulence = Some(recorded_at);
+        let downwind = recorded_at + Vector3::new(0.75, 0.0, 0.0);
+        assert_eq!(wind.velocity(downwind, 3.0), wind.velocity(recorded_at, 2.5));
+        assert_eq!(wind.velocity(recorded_at + Vector3::new(0.0, 1.0, 0.0), 2.5), wind.velocity(recorded_at, 2.5));
+        assert_ne!(wind.velocity(Vector3::zeros(), 2.5), wind.velocity(recorded_at, 2.5));
+    }
+}
@@ -0,0 +1,94 @@
+use nalgebra::Vector3;
+
+use crate::web::Spiderweb;
+
+/// Extended position based dynamics. Every strand is a distance constraint
+/// with a compliance of 1 / stiffness, so how stiff the web feels doesn't
+/// depend on the timestep. Fixed particles have no inverse mass and are never
+/// moved by a constraint.
+#[derive(Copy, Clone, Debug)]
+pub struct XpbdSolver {
+    /// Number of passes over the constraints per step
+    pub iterations: usize,
+}
+
+impl Default for XpbdSolver {
+    fn default() -> Self {
+        XpbdSolver {
+            iterations: 10,
+        }
+    }
+}
+
+impl XpbdSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Advances the web by one timestep. Returns the new positions and
+    /// velocities of the particles, and the force each strand pulled with.
+    ///
+    /// * `forces`: The external force on every particle at the start of the
+    ///   step. Strands are handled by the constraints and must not be included.
+    pub fn step(&self, web: &Spiderweb, forces: &[Vector3<f64>], timestep: f64) -> (Vec<Vector3<f64>>, Vec<Vector3<f64>>, Vec<f64>) {
+        let h = timestep;
+        let inverse_masses: Vec<f64> = web.particles.iter()
+            .map(|p| if p.fixed { 0.0 } else { 1.0 / p.mass })
+            .collect();
+
+        // Move every particle by its external forces, then project onto the constraints
+        let mut positions: Vec<Vector3<f64>> = web.particles.iter().enumerate()
+            .map(|(i, p)| p.position + (p.velocity + forces[i] * (inverse_masses[i] * h)) * h)
+            .collect();
+        let mut lambdas = vec![0.0; web.strands.len()];
+
+        for _ in 0..self.iterations {
+            for (i, strand) in web.strands.iter().enumerate() {
+                let (a, b) = (strand.start, strand.end);
+                let w = inverse_masses[a] + inverse_masses[b];
+                if w == 0.0 {
+                    continue;
+                }
+                let pos_diff = positions[a] - positions[b];
+                let len = pos_diff.norm();
+                if len < 1e-9 {
+                    continue;
+                }
+                let dir = pos_diff / len;
+
+                // Nonlinear silk is linearised about how far the strand was
+                // stretched at the start of the step. It pulls with its
+                // tension there, plus its tangent stiffness times however much
+                // further it stretches, which is a linear spring with this
+                // rest length.
+                let start_len = (web.particles[a].position - web.particles[b].position).norm();
+                let stiffness = strand.tangent_stiffness(start_len);
+                let rest_length = start_len - strand.tension(start_len) / stiffness;
+                let constraint = len - rest_length;
+                let compliance = 1.0 / (stiffness * h * h);
+                // Damping in the form given by Macklin et al., with the strand's
+                // damping as the dashpot coefficient
+                let gamma = strand.damping / (stiffness * h);
+                let relative_motion = dir.dot(&((positions[a] - web.particles[a].position) - (positions[b] - web.particles[b].position)));
+
+                let delta_lambda = (-constraint - compliance * lambdas[i] - gamma * relative_motion)
+                    / ((1.0 + gamma) * w + compliance);
+                lambdas[i] += delta_lambda;
+                positions[a] += dir * (delta_lambda * inverse_masses[a]);
+                positions[b] -= dir * (delta_lambda * inverse_masses[b]);
+            }
+        }
+
+        let mut new_velocities = Vec::with_capacity(positions.len());
+        for (i, particle) in web.particles.iter().enumerate() {
+            if particle.fixed {
+                positions[i] = particle.position;
+                new_velocities.push(particle.velocity);
+            } else {
+                new_velocities.push((positions[i] - particle.position) / h);
+            }
+        }
+        let strand_forces = lambdas.iter().map(|lambda| lambda.abs() / (h * h)).collect();
+        (positions, new_velocities, strand_forces)
+    }
+}